@@ -22,6 +22,8 @@ pub fn sync_libs(lib_path: &PathBuf) {
 /// Build the lib on posix platforms (using configure and make).
 /// Note, this function will not sync dependencies. use `sync_libs` or `sync_and_build_lib`.
 /// This function will also add the needed folder to the `link-search` path.
+/// If the `-sys` crate which is built has a `verbose_output` feature enabled,
+/// the library is built with its verbose and debug output (printed with libcnotify).
 /// Return the "include" folder for the library (to be used by bindgen).
 pub fn build_lib(lib_path: PathBuf, shared: bool) -> PathBuf {
     let target = lib_path.join("dist");
@@ -49,6 +51,12 @@ pub fn build_lib(lib_path: PathBuf, shared: bool) -> PathBuf {
         configure_cmd.arg("--enable-shared=no");
     }
 
+    if env::var_os("CARGO_FEATURE_VERBOSE_OUTPUT").is_some() {
+        configure_cmd
+            .arg("--enable-verbose-output")
+            .arg("--enable-debug-output");
+    }
+
     let status = configure_cmd.status().expect("configure failed");
 
     assert!(status.success(), "configure failed");
@@ -29,6 +29,8 @@ pub fn sync_libs(lib_path: &PathBuf) {
 /// Note, this function will not sync dependencies. use `sync_libs` or `sync_and_build_lib`.
 /// Require python to be installed.
 /// This function will also add the needed folder to the `link-search` path.
+/// If the `-sys` crate which is built has a `verbose_output` feature enabled,
+/// the library is built with its verbose and debug output (printed with libcnotify).
 /// Return the "include" folder for the library (to be used by bindgen).
 pub fn build_lib(lib_path: PathBuf, shared: bool) -> PathBuf {
     let python_exec = env::var("PYTHON_SYS_EXECUTABLE").unwrap_or_else(|_| "python.exe".to_owned());
@@ -109,6 +111,11 @@ pub fn build_lib(lib_path: PathBuf, shared: bool) -> PathBuf {
         msbuild.arg("/p:ConfigurationType=StaticLibrary");
     }
 
+    // The solution has no configuration with these, but `cl` reads extra options from `CL`.
+    if env::var_os("CARGO_FEATURE_VERBOSE_OUTPUT").is_some() {
+        msbuild.env("CL", "/DHAVE_VERBOSE_OUTPUT /DHAVE_DEBUG_OUTPUT");
+    }
+
     // We do not check status here because the Python bindings might failed to build,
     // but we don't care about that.
     let _status = msbuild.status().expect("Building the solution failed");
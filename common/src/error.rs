@@ -0,0 +1,351 @@
+//! A structured error of the native libyal libraries, shared by all the bindings.
+//!
+//! libyal errors are libcerror errors: a domain and a code, which are the ones of the root cause,
+//! and a backtrace of messages, from the root cause up to the function which was called.
+use libcerror_sys::*;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::os::raw::{c_char, c_int};
+
+/// The largest backtrace which is read, to stop growing the buffer of a failing `sprint`.
+const MAX_BACKTRACE_SIZE: usize = 16 * 1024 * 1024;
+
+// Error codes from `libcerror_definitions.h`, which are mapped to `io::ErrorKind`s.
+const IO_ERROR_ACCESS_DENIED: i32 = LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_ACCESS_DENIED as i32;
+const IO_ERROR_INVALID_RESOURCE: i32 =
+    LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_INVALID_RESOURCE as i32;
+const RUNTIME_ERROR_ABORT_REQUESTED: i32 =
+    LIBCERROR_RUNTIME_ERROR_LIBCERROR_RUNTIME_ERROR_ABORT_REQUESTED as i32;
+
+/// The libcerror error domains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorDomain {
+    Arguments,
+    Conversion,
+    Compression,
+    Io,
+    Input,
+    Memory,
+    Output,
+    Runtime,
+    Unknown,
+}
+
+impl ErrorDomain {
+    pub const ALL: [ErrorDomain; 8] = [
+        ErrorDomain::Arguments,
+        ErrorDomain::Conversion,
+        ErrorDomain::Compression,
+        ErrorDomain::Io,
+        ErrorDomain::Input,
+        ErrorDomain::Memory,
+        ErrorDomain::Output,
+        ErrorDomain::Runtime,
+    ];
+
+    /// The domain of a libcerror domain value, which is an ASCII character (like `'I'` for IO).
+    pub fn from_raw(domain: c_int) -> Self {
+        ErrorDomain::ALL
+            .iter()
+            .cloned()
+            .find(|candidate| candidate.to_raw() == domain)
+            .unwrap_or(ErrorDomain::Unknown)
+    }
+
+    pub fn to_raw(self) -> c_int {
+        let domain = match self {
+            ErrorDomain::Arguments => b'a',
+            ErrorDomain::Conversion => b'c',
+            ErrorDomain::Compression => b'C',
+            ErrorDomain::Io => b'I',
+            ErrorDomain::Input => b'i',
+            ErrorDomain::Memory => b'm',
+            ErrorDomain::Output => b'o',
+            ErrorDomain::Runtime => b'r',
+            ErrorDomain::Unknown => 0,
+        };
+
+        c_int::from(domain)
+    }
+
+    /// The largest error code of the domain, which is the last one in `libcerror_definitions.h`.
+    pub fn max_code(self) -> c_int {
+        let code = match self {
+            ErrorDomain::Arguments => {
+                LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_CONFLICTING_VALUE
+            }
+            ErrorDomain::Conversion => {
+                LIBCERROR_CONVERSION_ERROR_LIBCERROR_CONVERSION_ERROR_OUTPUT_FAILED
+            }
+            ErrorDomain::Compression => {
+                LIBCERROR_COMPRESSION_ERROR_LIBCERROR_COMPRESSION_ERROR_DECOMPRESS_FAILED
+            }
+            ErrorDomain::Io => LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_UNLINK_FAILED,
+            ErrorDomain::Input => LIBCERROR_INPUT_ERROR_LIBCERROR_INPUT_ERROR_VALUE_MISMATCH,
+            ErrorDomain::Memory => LIBCERROR_MEMORY_ERROR_LIBCERROR_MEMORY_ERROR_SET_FAILED,
+            ErrorDomain::Output => LIBCERROR_OUTPUT_ERROR_LIBCERROR_OUTPUT_ERROR_INSUFFICIENT_SPACE,
+            ErrorDomain::Runtime => LIBCERROR_RUNTIME_ERROR_LIBCERROR_RUNTIME_ERROR_ABORT_REQUESTED,
+            ErrorDomain::Unknown => return 0,
+        };
+
+        code as c_int
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeError {
+    domain: ErrorDomain,
+    code: i32,
+    frames: Vec<String>,
+}
+
+impl NativeError {
+    pub fn new(domain: ErrorDomain, code: i32, frames: Vec<String>) -> Self {
+        NativeError {
+            domain,
+            code,
+            frames,
+        }
+    }
+
+    /// Reads a libyal error.
+    ///
+    /// libcerror has no getters for the domain and code, so they are looked up with `matches`
+    /// (a `libcerror_error_matches` of the error), which is called for the codes of each domain
+    /// in turn, up to the largest code of the domain. This costs up to about 50 calls per error,
+    /// which is cheap compared to the backtrace. The frames are read with `backtrace_sprint`
+    /// (the `<library>_error_backtrace_sprint` of the error), with a buffer which is grown until
+    /// the whole backtrace fits. Returns `None` if the backtrace can't be printed.
+    pub fn read(
+        matches: impl Fn(c_int, c_int) -> bool,
+        backtrace_sprint: impl FnMut(*mut c_char, usize) -> c_int,
+    ) -> Option<Self> {
+        let backtrace = sprint_all(backtrace_sprint)?;
+
+        let (domain, code) = ErrorDomain::ALL
+            .iter()
+            .flat_map(|&domain| (0..=domain.max_code()).map(move |code| (domain, code)))
+            .find(|&(domain, code)| matches(domain.to_raw(), code))
+            .unwrap_or((ErrorDomain::Unknown, 0));
+
+        Some(NativeError::new(domain, code, parse_frames(&backtrace)))
+    }
+
+    /// The domain of the root cause.
+    pub fn domain(&self) -> ErrorDomain {
+        self.domain
+    }
+
+    /// The code of the root cause, within its domain.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// The message of the function which was called, like
+    /// `libfsntfs_volume_open: unable to open volume.`
+    pub fn message(&self) -> &str {
+        self.frames.last().map_or("", String::as_str)
+    }
+
+    /// The messages of the backtrace, from the root cause up to the function which was called.
+    pub fn frames(&self) -> &[String] {
+        &self.frames
+    }
+
+    /// Whether reading (or writing) the underlying storage failed.
+    pub fn is_io(&self) -> bool {
+        self.domain == ErrorDomain::Io
+    }
+
+    /// Whether the data is corrupt, or not in the format it was expected to be in.
+    pub fn is_corrupt(&self) -> bool {
+        matches!(
+            self.domain,
+            ErrorDomain::Input | ErrorDomain::Conversion | ErrorDomain::Compression
+        )
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        match (self.domain, self.code) {
+            (ErrorDomain::Io, IO_ERROR_ACCESS_DENIED) => io::ErrorKind::PermissionDenied,
+            (ErrorDomain::Io, IO_ERROR_INVALID_RESOURCE) => io::ErrorKind::NotFound,
+            (ErrorDomain::Runtime, RUNTIME_ERROR_ABORT_REQUESTED) => io::ErrorKind::Interrupted,
+            (ErrorDomain::Arguments, _) => io::ErrorKind::InvalidInput,
+            _ if self.is_corrupt() => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl Display for NativeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())?;
+
+        if f.alternate() {
+            for frame in self.frames.iter().rev().skip(1) {
+                write!(f, "\n  {}", frame)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for NativeError {}
+
+impl From<NativeError> for io::Error {
+    fn from(e: NativeError) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+/// The whole output of a libyal `sprint` function, which fails when the buffer is too small.
+fn sprint_all(mut sprint: impl FnMut(*mut c_char, usize) -> c_int) -> Option<String> {
+    let mut buffer: Vec<c_char> = vec![0; 1024];
+
+    loop {
+        if sprint(buffer.as_mut_ptr(), buffer.len()) != -1 {
+            let bytes: Vec<u8> = buffer
+                .iter()
+                .take_while(|&&c| c != 0)
+                .map(|&c| c as u8)
+                .collect();
+
+            return Some(String::from_utf8_lossy(&bytes).into_owned());
+        }
+
+        if buffer.len() >= MAX_BACKTRACE_SIZE {
+            return None;
+        }
+
+        buffer.resize(buffer.len() * 2, 0);
+    }
+}
+
+/// A backtrace has one message per line.
+fn parse_frames(backtrace: &str) -> Vec<String> {
+    backtrace
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::ptr;
+
+    const BACKTRACE: &str = "libcfile_file_open_with_error_code: no such file: image.dd.
+libbfio_file_open: unable to open file.
+libfsntfs_volume_open: unable to open volume.
+";
+
+    /// A `sprint` of `output`, which fails like libcerror if it doesn't fit.
+    fn sprint(output: &str) -> impl FnMut(*mut c_char, usize) -> c_int + '_ {
+        move |string, size| {
+            if output.len() + 1 > size {
+                return -1;
+            }
+
+            unsafe {
+                ptr::copy_nonoverlapping(output.as_ptr() as *const c_char, string, output.len());
+                *string.add(output.len()) = 0;
+            }
+
+            output.len() as c_int + 1
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let io = ErrorDomain::Io.to_raw();
+        let error =
+            NativeError::read(|domain, code| domain == io && code == 1, sprint(BACKTRACE)).unwrap();
+
+        assert_eq!(error.domain(), ErrorDomain::Io);
+        assert_eq!(error.code(), 1);
+        assert_eq!(error.frames().len(), 3);
+        assert_eq!(
+            error.message(),
+            "libfsntfs_volume_open: unable to open volume."
+        );
+        assert!(error.is_io());
+        assert!(!error.is_corrupt());
+    }
+
+    #[test]
+    fn test_deep_backtraces_are_not_truncated() {
+        let backtrace = "libfsntfs_mft_entry_read: unable to read MFT entry.\n".repeat(1000);
+        let error = NativeError::read(|_, _| false, sprint(&backtrace)).unwrap();
+
+        assert_eq!(error.frames().len(), 1000);
+        assert_eq!(error.domain(), ErrorDomain::Unknown);
+        assert!(NativeError::read(|_, _| false, |_, _| -1).is_none());
+    }
+
+    #[test]
+    fn test_codes_are_looked_up_up_to_the_largest_code_of_each_domain() {
+        let calls = Cell::new(0);
+        let error = NativeError::read(
+            |_, _| {
+                calls.set(calls.get() + 1);
+                false
+            },
+            sprint(BACKTRACE),
+        )
+        .unwrap();
+
+        assert_eq!(error.domain(), ErrorDomain::Unknown);
+        assert!(calls.get() < 64);
+
+        let runtime = ErrorDomain::Runtime.to_raw();
+        let aborted = NativeError::read(
+            |domain, code| domain == runtime && code == RUNTIME_ERROR_ABORT_REQUESTED,
+            sprint(BACKTRACE),
+        )
+        .unwrap();
+
+        assert_eq!(aborted.domain(), ErrorDomain::Runtime);
+        assert_eq!(aborted.kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn test_io_error_kind() {
+        let corrupt = NativeError::new(ErrorDomain::Input, 3, vec!["invalid signature.".into()]);
+        let denied = NativeError::new(ErrorDomain::Io, IO_ERROR_ACCESS_DENIED, vec![]);
+
+        assert!(corrupt.is_corrupt());
+        assert_eq!(io::Error::from(corrupt).kind(), io::ErrorKind::InvalidData);
+        assert_eq!(denied.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            NativeError::new(ErrorDomain::Runtime, 12, vec![]).kind(),
+            io::ErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_domains() {
+        for &domain in ErrorDomain::ALL.iter() {
+            assert_eq!(ErrorDomain::from_raw(domain.to_raw()), domain);
+        }
+
+        assert_eq!(ErrorDomain::from_raw(c_int::from(b'I')), ErrorDomain::Io);
+        assert_eq!(ErrorDomain::from_raw(0), ErrorDomain::Unknown);
+    }
+
+    #[test]
+    fn test_display() {
+        let error = NativeError::read(|_, _| false, sprint(BACKTRACE)).unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "libfsntfs_volume_open: unable to open volume."
+        );
+        assert!(format!("{:#}", error)
+            .ends_with("  libcfile_file_open_with_error_code: no such file: image.dd."));
+    }
+}
@@ -140,3 +140,70 @@ macro_rules! get_u64_field {
         }
     }};
 }
+
+#[macro_export]
+macro_rules! get_u32_field {
+    ($self: ident, $getter: ident) => {{
+        let mut value = 0_u32;
+        let mut error = ptr::null_mut();
+
+        if unsafe { $getter($self.as_type_ref(), &mut value, &mut error) } != 1 {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(value)
+        }
+    }};
+}
+
+/// Like `get_sized_utf8_string`, but for getters which return 0 when the value is not available.
+/// Returns early with `Ok(None)` in that case, so it should be used as the body of a getter.
+#[macro_export]
+macro_rules! get_optional_sized_utf8_string {
+    ($self: ident, $get_size: ident, $get_string: ident) => {{
+        let mut name_size = 0_usize;
+        let mut error = ptr::null_mut();
+
+        match unsafe { $get_size($self.as_type_ref(), &mut name_size, &mut error) } {
+            1 => {}
+            0 => return Ok(None),
+            _ => return Err(Error::try_from(error)?),
+        };
+
+        if name_size == 0 {
+            Ok(Some(String::new()))
+        } else {
+            let mut name = vec![0; name_size];
+            let mut error = ptr::null_mut();
+
+            if unsafe {
+                $get_string(
+                    $self.as_type_ref(),
+                    name.as_mut_ptr(),
+                    name.len(),
+                    &mut error,
+                )
+            } != 1
+            {
+                Err(Error::try_from(error)?)
+            } else {
+                // Discard nul terminator;
+                name.pop().expect("name_size was checked to be > 0");
+                let s = String::from_utf8(name).map_err(|e| Error::StringContainsInvalidUTF8(e))?;
+                Ok(Some(s))
+            }
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! get_bool_field {
+    ($self: ident, $getter: ident) => {{
+        let mut error = ptr::null_mut();
+
+        match unsafe { $getter($self.as_type_ref(), &mut error) } {
+            1 => Ok(true),
+            0 => Ok(false),
+            _ => Err(Error::try_from(error)?),
+        }
+    }};
+}
@@ -1 +1,2 @@
-pub mod ffi;
\ No newline at end of file
+pub mod error;
+pub mod ffi;
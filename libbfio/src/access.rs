@@ -0,0 +1,197 @@
+//! Combinable access flags, in the style of `OpenOptions`.
+//!
+//! libbfio only knows read, write and truncate access. Creating a missing file is only known
+//! on the Rust side, and both creating and truncating imply write access.
+use crate::handle::LibbfioAccessFlags;
+use libbfio_sys::{
+    LIBBFIO_ACCESS_FLAGS_LIBBFIO_ACCESS_FLAG_READ,
+    LIBBFIO_ACCESS_FLAGS_LIBBFIO_ACCESS_FLAG_TRUNCATE,
+    LIBBFIO_ACCESS_FLAGS_LIBBFIO_ACCESS_FLAG_WRITE,
+};
+use std::fs::OpenOptions;
+use std::os::raw::c_int;
+
+const READ_FLAG: c_int = LIBBFIO_ACCESS_FLAGS_LIBBFIO_ACCESS_FLAG_READ as c_int;
+const WRITE_FLAG: c_int = LIBBFIO_ACCESS_FLAGS_LIBBFIO_ACCESS_FLAG_WRITE as c_int;
+const TRUNCATE_FLAG: c_int = LIBBFIO_ACCESS_FLAGS_LIBBFIO_ACCESS_FLAG_TRUNCATE as c_int;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccessFlags {
+    read: bool,
+    write: bool,
+    create: bool,
+    truncate: bool,
+}
+
+impl AccessFlags {
+    /// No access at all, to be combined with the builder methods.
+    pub fn new() -> Self {
+        AccessFlags::default()
+    }
+
+    pub fn read_only() -> Self {
+        AccessFlags::new().read(true)
+    }
+
+    /// Read and write access to an existing file, which allows read-modify-write.
+    pub fn read_write() -> Self {
+        AccessFlags::new().read(true).write(true)
+    }
+
+    pub fn read(mut self, read: bool) -> Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(mut self, write: bool) -> Self {
+        self.write = write;
+        self
+    }
+
+    /// Creates the file if it is missing. Implies write access.
+    pub fn create(mut self, create: bool) -> Self {
+        self.create = create;
+        self
+    }
+
+    /// Truncates the file when it is opened. Implies write access.
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.truncate = truncate;
+        self
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.read
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.write || self.create || self.truncate
+    }
+
+    pub fn is_create(&self) -> bool {
+        self.create
+    }
+
+    pub fn is_truncate(&self) -> bool {
+        self.truncate
+    }
+
+    /// The libbfio access flags, which can't express `create`.
+    pub fn to_int(&self) -> c_int {
+        let mut flags = 0;
+
+        if self.read {
+            flags |= READ_FLAG;
+        }
+        if self.is_writable() {
+            flags |= WRITE_FLAG;
+        }
+        if self.truncate {
+            flags |= TRUNCATE_FLAG;
+        }
+
+        flags
+    }
+
+    /// The access flags for the libbfio `access_flags`.
+    pub fn from_int(access_flags: c_int) -> Self {
+        AccessFlags::new()
+            .read(access_flags & READ_FLAG != 0)
+            .write(access_flags & WRITE_FLAG != 0)
+            .truncate(access_flags & TRUNCATE_FLAG != 0)
+    }
+
+    /// The options to open a file with these access flags.
+    pub fn open_options(&self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+
+        options
+            .read(self.read)
+            .write(self.is_writable())
+            .create(self.create)
+            .truncate(self.truncate);
+
+        options
+    }
+}
+
+/// `Truncate` creates missing files, like it does in libbfio.
+impl From<LibbfioAccessFlags> for AccessFlags {
+    fn from(flags: LibbfioAccessFlags) -> Self {
+        match flags {
+            LibbfioAccessFlags::Read => AccessFlags::read_only(),
+            LibbfioAccessFlags::Write => AccessFlags::new().write(true),
+            LibbfioAccessFlags::Truncate => AccessFlags::new().create(true).truncate(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_libbfio_flags() {
+        assert_eq!(AccessFlags::read_only().to_int(), READ_FLAG);
+        assert_eq!(AccessFlags::read_write().to_int(), READ_FLAG | WRITE_FLAG);
+        assert_eq!(AccessFlags::new().create(true).to_int(), WRITE_FLAG);
+        assert_eq!(
+            AccessFlags::new().truncate(true).to_int(),
+            WRITE_FLAG | TRUNCATE_FLAG
+        );
+
+        let flags = AccessFlags::read_write().truncate(true);
+        assert_eq!(AccessFlags::from_int(flags.to_int()), flags);
+        assert_eq!(
+            AccessFlags::from(LibbfioAccessFlags::Write).to_int(),
+            LibbfioAccessFlags::Write.to_int()
+        );
+    }
+
+    #[test]
+    fn test_read_modify_write() {
+        let dir = TempDir::new("access").unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, b"Hello world").unwrap();
+
+        let mut file = AccessFlags::read_write()
+            .open_options()
+            .open(&path)
+            .unwrap();
+        let mut buf = [0; 5];
+
+        file.read_exact(&mut buf).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(&buf.to_ascii_uppercase()).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"HELLO world");
+    }
+
+    #[test]
+    fn test_create_and_truncate() {
+        let dir = TempDir::new("access").unwrap();
+        let path = dir.path().join("file");
+
+        assert!(AccessFlags::read_write()
+            .open_options()
+            .open(&path)
+            .is_err());
+
+        AccessFlags::new()
+            .create(true)
+            .open_options()
+            .open(&path)
+            .unwrap();
+        assert!(path.exists());
+
+        fs::write(&path, b"Hello world").unwrap();
+        AccessFlags::from(LibbfioAccessFlags::Truncate)
+            .open_options()
+            .open(&path)
+            .unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,434 @@
+//! A block cache for IO sources, with sequential readahead.
+//!
+//! Reads are served from fixed-size pages, which are read from the source on a miss
+//! and evicted least recently used first.
+//! When a miss directly follows the pages read from the source before it, the next pages are read
+//! along with it, in a single request. Writes go straight through to the source.
+use crate::seek::seek_position;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Options of a `BlockCache`.
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    page_size: usize,
+    capacity: usize,
+    readahead: usize,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        CacheOptions {
+            page_size: 32 * 1024,
+            capacity: 256,
+            readahead: 4,
+        }
+    }
+}
+
+impl CacheOptions {
+    pub fn new() -> Self {
+        CacheOptions::default()
+    }
+
+    /// The size of a page in bytes. Defaults to 32 KiB.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// The maximal number of cached pages. Defaults to 256.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// The number of pages read ahead of a sequential read. Defaults to 4, 0 disables readahead.
+    pub fn readahead(mut self, readahead: usize) -> Self {
+        self.readahead = readahead;
+        self
+    }
+}
+
+/// Hit and miss counters of a `BlockCache`, which can be shared with other threads.
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    readahead_pages: AtomicU64,
+}
+
+impl CacheStats {
+    pub fn new() -> Self {
+        CacheStats::default()
+    }
+
+    /// The number of page lookups served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.0.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of page lookups which had to read from the source.
+    pub fn misses(&self) -> u64 {
+        self.0.misses.load(Ordering::Relaxed)
+    }
+
+    /// The number of pages read from the source ahead of being needed.
+    pub fn readahead_pages(&self) -> u64 {
+        self.0.readahead_pages.load(Ordering::Relaxed)
+    }
+
+    /// The ratio of page lookups served from the cache, 0 before any lookup.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits() + self.misses();
+
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits() as f64 / lookups as f64
+        }
+    }
+
+    pub fn reset(&self) {
+        self.0.hits.store(0, Ordering::Relaxed);
+        self.0.misses.store(0, Ordering::Relaxed);
+        self.0.readahead_pages.store(0, Ordering::Relaxed);
+    }
+
+    fn add(counter: &AtomicU64, count: u64) {
+        counter.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+struct Page {
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+pub struct BlockCache<T> {
+    inner: T,
+    options: CacheOptions,
+    pages: HashMap<u64, Page>,
+    /// Page indices by the tick they were last used at, least recently used first.
+    lru: BTreeMap<u64, u64>,
+    tick: u64,
+    /// The page following the last pages read from the source, to detect sequential reads.
+    next_sequential_page: Option<u64>,
+    position: u64,
+    len: Option<u64>,
+    stats: CacheStats,
+}
+
+impl<T> BlockCache<T> {
+    pub fn new(inner: T, options: CacheOptions) -> Self {
+        BlockCache::with_stats(inner, options, CacheStats::new())
+    }
+
+    /// A cache which counts its hits and misses in `stats`, which may be shared with other caches.
+    pub fn with_stats(inner: T, options: CacheOptions, stats: CacheStats) -> Self {
+        BlockCache {
+            inner,
+            options,
+            pages: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            next_sequential_page: None,
+            position: 0,
+            len: None,
+            stats,
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats.clone()
+    }
+
+    /// Drops all the cached pages, for when the source was modified by other means.
+    pub fn invalidate(&mut self) {
+        self.pages.clear();
+        self.lru.clear();
+        self.next_sequential_page = None;
+        self.len = None;
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn page_size(&self) -> u64 {
+        self.options.page_size as u64
+    }
+
+    fn touch(&mut self, index: u64) {
+        self.tick += 1;
+
+        if let Some(page) = self.pages.get_mut(&index) {
+            self.lru.remove(&page.last_used);
+            page.last_used = self.tick;
+            self.lru.insert(self.tick, index);
+        }
+    }
+
+    fn insert(&mut self, index: u64, data: Vec<u8>) {
+        self.remove(index);
+
+        while self.pages.len() >= self.options.capacity {
+            let oldest = match self.lru.iter().next() {
+                Some((_, &oldest)) => oldest,
+                None => break,
+            };
+            self.remove(oldest);
+        }
+
+        self.tick += 1;
+        self.pages.insert(
+            index,
+            Page {
+                data,
+                last_used: self.tick,
+            },
+        );
+        self.lru.insert(self.tick, index);
+    }
+
+    fn remove(&mut self, index: u64) {
+        if let Some(page) = self.pages.remove(&index) {
+            self.lru.remove(&page.last_used);
+        }
+    }
+}
+
+impl<T: Read + Seek> BlockCache<T> {
+    fn len(&mut self) -> io::Result<u64> {
+        match self.len {
+            Some(len) => Ok(len),
+            None => {
+                let len = self.inner.seek(SeekFrom::End(0))?;
+                self.len = Some(len);
+                Ok(len)
+            }
+        }
+    }
+
+    /// The page at `index`, which is empty past the end of the source.
+    fn page(&mut self, index: u64) -> io::Result<&[u8]> {
+        if self.pages.contains_key(&index) {
+            CacheStats::add(&self.stats.0.hits, 1);
+            self.touch(index);
+        } else {
+            CacheStats::add(&self.stats.0.misses, 1);
+
+            let count = if self.next_sequential_page == Some(index) {
+                (1 + self.options.readahead).min(self.options.capacity)
+            } else {
+                1
+            };
+
+            self.fetch(index, count)?;
+        }
+
+        Ok(&self.pages[&index].data)
+    }
+
+    /// Reads `count` pages from the source, starting at the page at `first`, in a single request.
+    fn fetch(&mut self, first: u64, count: usize) -> io::Result<()> {
+        let page_size = self.options.page_size;
+        let start = first * self.page_size();
+        let mut buf = vec![0; page_size * count];
+        let mut filled = 0;
+
+        self.inner.seek(SeekFrom::Start(start))?;
+
+        while filled < buf.len() {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) => {
+                    self.len = Some(start + filled as u64);
+                    break;
+                }
+                Ok(read_count) => filled += read_count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        buf.truncate(filled);
+
+        // The requested page is inserted last, so readahead never evicts it.
+        let mut chunks: Vec<Vec<u8>> = buf.chunks(page_size).map(|c| c.to_vec()).collect();
+        let requested = if chunks.is_empty() {
+            vec![]
+        } else {
+            chunks.remove(0)
+        };
+
+        CacheStats::add(&self.stats.0.readahead_pages, chunks.len() as u64);
+
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            self.insert(first + 1 + i as u64, chunk);
+        }
+        self.insert(first, requested);
+
+        self.next_sequential_page = Some(first + count as u64);
+
+        Ok(())
+    }
+}
+
+impl<T: Read + Seek> Read for BlockCache<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut total = 0;
+
+        while total < buf.len() {
+            let index = self.position / self.page_size();
+            let offset = (self.position % self.page_size()) as usize;
+
+            let page = self.page(index)?;
+
+            if offset >= page.len() {
+                break;
+            }
+
+            let count = (page.len() - offset).min(buf.len() - total);
+            buf[total..total + count].copy_from_slice(&page[offset..offset + count]);
+
+            total += count;
+            self.position += count as u64;
+        }
+
+        Ok(total)
+    }
+}
+
+impl<T: Write + Seek> Write for BlockCache<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.seek(SeekFrom::Start(self.position))?;
+        let count = self.inner.write(buf)?;
+
+        if count > 0 {
+            let first = self.position / self.page_size();
+            let last = (self.position + count as u64 - 1) / self.page_size();
+
+            for index in first..=last {
+                self.remove(index);
+            }
+        }
+
+        self.position += count as u64;
+
+        if let Some(len) = self.len {
+            self.len = Some(len.max(self.position));
+        }
+
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Read + Seek> Seek for BlockCache<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // Finding the length of the source may take a seek, so it is only done when needed.
+        let len = match pos {
+            SeekFrom::End(_) => self.len()?,
+            _ => 0,
+        };
+
+        self.position = seek_position(self.position, len, pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn data() -> Vec<u8> {
+        (0..100).collect()
+    }
+
+    fn cache(options: CacheOptions) -> BlockCache<Cursor<Vec<u8>>> {
+        BlockCache::new(Cursor::new(data()), options)
+    }
+
+    #[test]
+    fn test_read_across_pages() {
+        let mut cache = cache(CacheOptions::new().page_size(16));
+        let mut buf = vec![];
+
+        cache.seek(SeekFrom::Start(10)).unwrap();
+        cache.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, &data()[10..]);
+        assert_eq!(cache.seek(SeekFrom::End(-1)).unwrap(), 99);
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = cache(CacheOptions::new().page_size(16).readahead(0));
+        let mut buf = [0; 4];
+
+        cache.read_exact(&mut buf).unwrap();
+        cache.read_exact(&mut buf).unwrap();
+        cache.seek(SeekFrom::Start(40)).unwrap();
+        cache.read_exact(&mut buf).unwrap();
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits(), 1);
+        assert_eq!(stats.misses(), 2);
+        assert_eq!(&buf, &[40, 41, 42, 43]);
+    }
+
+    #[test]
+    fn test_least_recently_used_page_is_evicted() {
+        let mut cache = cache(CacheOptions::new().page_size(10).capacity(2).readahead(0));
+        let mut buf = [0; 1];
+
+        for &offset in &[0, 20, 0, 40, 0, 20] {
+            cache.seek(SeekFrom::Start(offset)).unwrap();
+            cache.read_exact(&mut buf).unwrap();
+        }
+
+        // Pages 0, 2 and 4 miss, page 0 hits twice and page 2 was evicted by page 4.
+        assert_eq!(cache.stats().hits(), 2);
+        assert_eq!(cache.stats().misses(), 4);
+    }
+
+    #[test]
+    fn test_sequential_reads_read_ahead() {
+        let mut cache = cache(CacheOptions::new().page_size(10).readahead(3));
+        let mut buf = [0; 10];
+
+        for _ in 0..6 {
+            cache.read_exact(&mut buf).unwrap();
+        }
+
+        // Page 0 and 1 miss, which reads pages 2-4 ahead, and page 5 reads ahead again.
+        let stats = cache.stats();
+        assert_eq!(stats.misses(), 3);
+        assert_eq!(stats.hits(), 3);
+        assert_eq!(stats.readahead_pages(), 6);
+        assert_eq!(&buf, &data()[50..60]);
+    }
+
+    #[test]
+    fn test_write_invalidates_pages() {
+        let mut cache = cache(CacheOptions::new().page_size(16));
+        let mut buf = [0; 4];
+
+        cache.read_exact(&mut buf).unwrap();
+        cache.seek(SeekFrom::Start(2)).unwrap();
+        cache.write_all(b"ab").unwrap();
+        cache.seek(SeekFrom::Start(0)).unwrap();
+        cache.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, &[0, 1, b'a', b'b']);
+    }
+}
@@ -1,4 +1,5 @@
 use failure::Fail;
+use libyal_rs_common::error::NativeError;
 
 use std::ffi::{FromBytesWithNulError, NulError};
 use std::string::FromUtf8Error;
@@ -18,8 +19,20 @@ pub enum Error {
     StringContainsNul(#[cause] NulError),
     #[fail(display = "Failed to open file: {}", _0)]
     FailedToOpenFile(#[cause] io::Error),
+    #[fail(display = "An IO error has occurred: {}", _0)]
+    Io(#[cause] io::Error),
     #[fail(display = "An FFI error has occurred: {}", _0)]
-    FFI(String),
+    FFI(#[cause] NativeError),
     #[fail(display = "An unexpected error has occurred: {}", _0)]
     Other(String),
 }
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::FFI(native) => native.into(),
+            Error::Io(e) | Error::FailedToOpenFile(e) => e,
+            e => io::Error::new(io::ErrorKind::Other, e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! An IO source which fails on purpose, to test (and fuzz) error handling.
+//!
+//! Reads or seeks fail at configured offsets, or once a number of calls succeeded.
+//! Failed calls don't move the position.
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+pub struct FaultInjector<T> {
+    inner: T,
+    failing_reads: Vec<Range<u64>>,
+    failing_seeks: Vec<u64>,
+    reads_until_failure: Option<u64>,
+    seeks_until_failure: Option<u64>,
+    position: u64,
+    reads: u64,
+    seeks: u64,
+}
+
+impl<T> FaultInjector<T> {
+    pub fn new(inner: T) -> Self {
+        FaultInjector {
+            inner,
+            failing_reads: vec![],
+            failing_seeks: vec![],
+            reads_until_failure: None,
+            seeks_until_failure: None,
+            position: 0,
+            reads: 0,
+            seeks: 0,
+        }
+    }
+
+    /// Fails reads of any of the bytes in `range`.
+    pub fn fail_reads_in(mut self, range: Range<u64>) -> Self {
+        self.failing_reads.push(range);
+        self
+    }
+
+    /// Fails seeks to `offset`.
+    pub fn fail_seeks_to(mut self, offset: u64) -> Self {
+        self.failing_seeks.push(offset);
+        self
+    }
+
+    /// Fails all the reads after the first `count` ones.
+    pub fn fail_reads_after(mut self, count: u64) -> Self {
+        self.reads_until_failure = Some(count);
+        self
+    }
+
+    /// Fails all the seeks after the first `count` ones.
+    pub fn fail_seeks_after(mut self, count: u64) -> Self {
+        self.seeks_until_failure = Some(count);
+        self
+    }
+
+    /// The number of reads so far, including the failed ones.
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// The number of seeks so far, including the failed ones.
+    pub fn seeks(&self) -> u64 {
+        self.seeks
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn injected(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+impl<T: Read> Read for FaultInjector<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads += 1;
+
+        if let Some(count) = self.reads_until_failure {
+            if self.reads > count {
+                return Err(injected(format!("injected failure of read {}", self.reads)));
+            }
+        }
+
+        let end = self.position.saturating_add(buf.len() as u64);
+
+        if !buf.is_empty()
+            && self
+                .failing_reads
+                .iter()
+                .any(|range| range.start < end && self.position < range.end)
+        {
+            return Err(injected(format!(
+                "injected read failure at offset {}",
+                self.position
+            )));
+        }
+
+        let count = self.inner.read(buf)?;
+        self.position += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl<T: Seek> Seek for FaultInjector<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.seeks += 1;
+
+        if let Some(count) = self.seeks_until_failure {
+            if self.seeks > count {
+                return Err(injected(format!("injected failure of seek {}", self.seeks)));
+            }
+        }
+
+        let new_position = self.inner.seek(pos)?;
+
+        if self.failing_seeks.contains(&new_position) {
+            self.inner.seek(SeekFrom::Start(self.position))?;
+
+            return Err(injected(format!(
+                "injected seek failure to offset {}",
+                new_position
+            )));
+        }
+
+        self.position = new_position;
+
+        Ok(new_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn injector() -> FaultInjector<Cursor<Vec<u8>>> {
+        FaultInjector::new(Cursor::new((0..100).collect()))
+    }
+
+    #[test]
+    fn test_fail_reads_in_range() {
+        let mut source = injector().fail_reads_in(50..60);
+        let mut buf = [0; 10];
+
+        source.read_exact(&mut buf).unwrap();
+
+        source.seek(SeekFrom::Start(45)).unwrap();
+        assert!(source.read(&mut buf).is_err());
+
+        source.seek(SeekFrom::Start(60)).unwrap();
+        source.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 60);
+    }
+
+    #[test]
+    fn test_fail_seeks_to_offset() {
+        let mut source = injector().fail_seeks_to(20);
+
+        assert!(source.seek(SeekFrom::Start(20)).is_err());
+        assert_eq!(source.seek(SeekFrom::Current(0)).unwrap(), 0);
+        assert!(source.seek(SeekFrom::Start(21)).is_ok());
+    }
+
+    #[test]
+    fn test_fail_after_calls() {
+        let mut source = injector().fail_reads_after(2).fail_seeks_after(1);
+        let mut buf = [0; 1];
+
+        source.read_exact(&mut buf).unwrap();
+        source.read_exact(&mut buf).unwrap();
+        assert!(source.read(&mut buf).is_err());
+        assert_eq!(source.reads(), 3);
+
+        source.seek(SeekFrom::Start(0)).unwrap();
+        assert!(source.seek(SeekFrom::Start(0)).is_err());
+    }
+}
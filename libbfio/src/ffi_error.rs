@@ -1,9 +1,10 @@
 use crate::error::Error;
 use libbfio_sys::FILE;
+use libcerror_sys::libcerror_error_matches;
+use libyal_rs_common::error::NativeError;
 use libyal_rs_common::ffi::AsTypeRef;
 use log::trace;
 use std::convert::TryFrom;
-use std::ffi::{CStr};
 
 
 
@@ -65,6 +66,7 @@ impl Drop for LibbfioError {
     }
 }
 
+/// Takes ownership of the error, which is freed once it was read.
 impl TryFrom<*mut __LibbfioError> for Error {
     type Error = Error;
 
@@ -73,16 +75,17 @@ impl TryFrom<*mut __LibbfioError> for Error {
             return Err(Error::Other("Error pointer cannot be NULL".to_owned()));
         }
 
-        let mut buffer = vec![0; 1024];
+        let err = LibbfioError(err);
 
-        let retcode =
-            unsafe { libbfio_error_sprint(err as *const _, buffer.as_mut_ptr(), buffer.len()) };
+        let native = NativeError::read(
+            |domain, code| unsafe { libcerror_error_matches(err.0 as _, domain, code) == 1 },
+            |string, size| unsafe {
+                libbfio_error_backtrace_sprint(err.as_type_ref(), string, size)
+            },
+        );
 
-        if retcode == -1 {
-            Err(Error::FFI("Failed to print error".to_owned()))
-        } else {
-            let repr = unsafe { CStr::from_ptr(buffer.as_ptr()) };
-            Ok(Error::FFI(repr.to_string_lossy().to_string()))
-        }
+        native
+            .map(Error::FFI)
+            .ok_or_else(|| Error::Other("Failed to print error".to_owned()))
     }
 }
@@ -3,20 +3,28 @@
 //! The `Handle` abstracts over concrete implementation of a data IO handle.
 //! We use it to wrap a rust IO handle which in itself is a Boxed, dynamically dispatched IO source.
 //!
+use crate::cache::{CacheOptions, CacheStats};
 use crate::error::Error;
 use crate::ffi_error::LibbfioErrorRefMut;
+#[cfg(feature = "http")]
+use crate::http::HttpOptions;
 use crate::io_handle::IoHandle;
 use crate::io_handle::*;
+use crate::overlay::Overlay;
+use crate::segments::discover_segments;
+use crate::window::Window;
+pub use crate::access::AccessFlags;
+pub use crate::io_handle::IoStats;
 use libyal_rs_common::ffi::AsTypeRef;
 
 use libbfio_sys::*;
 use std::convert::TryFrom;
 
-use crate::error::Error::FailedToOpenFile;
-use std::fs::{File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::{Deref, DerefMut, Range};
 use std::os::raw::c_int;
 use std::path::Path;
+use std::sync::Arc;
 use std::{io, ptr};
 
 #[repr(C)]
@@ -54,6 +62,11 @@ impl Handle {
     }
 }
 
+// A `Handle` exclusively owns the libbfio handle and the (`Send`) IO handle inside it,
+// and libbfio handles are not bound to the thread that created them.
+// It is not `Sync`, since libbfio handles keep an unsynchronized current offset.
+unsafe impl Send for Handle {}
+
 #[repr(C)]
 enum LibbfioIoHandleType {
     /* The IO handle is not managed by the library
@@ -136,10 +149,10 @@ extern "C" {
         seek_offset: Option<
             unsafe extern "C" fn(
                 io_handle: *mut IoHandle,
-                offset: u64,
+                offset: i64,
                 whence: c_int,
                 error: *mut LibbfioErrorRefMut,
-            ) -> u64,
+            ) -> i64,
         >,
         exists: Option<
             unsafe extern "C" fn(io_handle: *mut IoHandle, error: *mut LibbfioErrorRefMut) -> c_int,
@@ -181,6 +194,13 @@ extern "C" {
         size: usize,
         error: *mut LibbfioErrorRefMut,
     ) -> isize;
+    pub fn libbfio_handle_read_buffer_at_offset(
+        handle: HandleRef,
+        buffer: *mut u8,
+        size: usize,
+        offset: i64,
+        error: *mut LibbfioErrorRefMut,
+    ) -> isize;
     pub fn libbfio_handle_write_buffer(
         handle: HandleRef,
         buffer: *const u8,
@@ -189,15 +209,15 @@ extern "C" {
     ) -> isize;
     pub fn libbfio_handle_seek_offset(
         handle: HandleRef,
-        offset: u64,
+        offset: i64,
         whence: c_int,
         error: *mut LibbfioErrorRefMut,
-    ) -> u64;
+    ) -> i64;
     pub fn libbfio_handle_exists(handle: HandleRef, error: *mut LibbfioErrorRefMut) -> c_int;
     pub fn libbfio_handle_is_open(handle: HandleRef, error: *mut LibbfioErrorRefMut) -> c_int;
     pub fn libbfio_handle_get_io_handle(
         handle: HandleRef,
-        io_handle: *mut HandleRefMut,
+        io_handle: *mut *mut IoHandle,
         error: *mut LibbfioErrorRefMut,
     ) -> c_int;
     pub fn libbfio_handle_get_access_flags(
@@ -245,17 +265,319 @@ extern "C" {
 }
 
 impl Handle {
-    pub fn open_file(path: impl AsRef<Path>, flags: LibbfioAccessFlags) -> Result<Handle, Error> {
-        let f = match flags {
-            LibbfioAccessFlags::Read => OpenOptions::new().read(true).open(path),
-            LibbfioAccessFlags::Write => OpenOptions::new().write(true).open(path),
-            LibbfioAccessFlags::Truncate => OpenOptions::new().create(true).open(path),
+    /// Opens the file at `path`.
+    /// The file is re-opened by path when the handle is re-opened or cloned,
+    /// so it can be used with open-on-demand.
+    pub fn open_file(
+        path: impl AsRef<Path>,
+        flags: impl Into<AccessFlags>,
+    ) -> Result<Handle, Error> {
+        let flags = flags.into();
+        let io_handle = IoHandle::path(path, flags).map_err(Error::FailedToOpenFile)?;
+
+        Handle::from_io_handle(io_handle, flags.to_int())
+    }
+
+    /// Opens the file at `path`, with a block cache (and readahead) in front of it.
+    /// This helps with the small, scattered reads of parsers on slow storage.
+    ///
+    /// `read_ranges` and `io_stats` record the reads made through the handle, which are served
+    /// by the cache, so they don't include the pages the cache reads ahead from the file
+    /// (see `cache_stats`). Use `open_file` to record exactly which bytes of the file are read.
+    pub fn open_file_cached(
+        path: impl AsRef<Path>,
+        flags: impl Into<AccessFlags>,
+        options: &CacheOptions,
+    ) -> Result<Handle, Error> {
+        let flags = flags.into();
+        let io_handle = IoHandle::cached_path(path, flags, options)
+            .map_err(Error::FailedToOpenFile)?;
+
+        Handle::from_io_handle(io_handle, flags.to_int())
+    }
+
+    /// Opens the file at `path` as a read-only memory map,
+    /// which saves a syscall for every read of a large image.
+    pub fn open_mmap(path: impl AsRef<Path>) -> Result<Handle, Error> {
+        let io_handle = IoHandle::mmap(path).map_err(Error::FailedToOpenFile)?;
+
+        Handle::from_io_handle(io_handle, LibbfioAccessFlags::Read.to_int())
+    }
+
+    /// Opens a split raw image (`image.001`, `image.002`, ...) as a single read-only handle.
+    /// The rest of the segments are discovered from the name of `first_segment`.
+    pub fn open_segments(first_segment: impl AsRef<Path>) -> Result<Handle, Error> {
+        let paths = discover_segments(first_segment).map_err(Error::FailedToOpenFile)?;
+        let io_handle = IoHandle::segments(paths).map_err(Error::FailedToOpenFile)?;
+
+        Handle::from_io_handle(io_handle, LibbfioAccessFlags::Read.to_int())
+    }
+
+    /// Opens the resource at `url` as a read-only handle, which reads with HTTP `Range` requests.
+    /// Like with `open_file_cached`, `read_ranges` and `io_stats` don't include the pages
+    /// the block cache of the handle reads ahead.
+    #[cfg(feature = "http")]
+    pub fn open_url(url: &str, options: &HttpOptions) -> Result<Handle, Error> {
+        let io_handle = IoHandle::url(url, options).map_err(Error::FailedToOpenFile)?;
+
+        Handle::from_io_handle(io_handle, LibbfioAccessFlags::Read.to_int())
+    }
+
+    /// Creates a read-only handle over shared, in-memory data.
+    /// Unlike `from_reader`, the handle can be cloned without copying the data.
+    pub fn from_bytes(data: impl Into<Arc<[u8]>>) -> Result<Handle, Error> {
+        Handle::from_io_handle(IoHandle::bytes(data.into()), LibbfioAccessFlags::Read.to_int())
+    }
+
+    /// Creates a new handle to the same source, with an offset of its own, starting at 0.
+    /// Only handles which can re-create their source (`open_file` and `from_bytes`) can be cloned.
+    pub fn try_clone(&self) -> Result<Handle, Error> {
+        let mut handle = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        if unsafe { libbfio_handle_clone(&mut handle, self.as_type_ref(), &mut error) } != 1 {
+            return Err(Error::try_from(error)?);
+        }
+
+        let mut clone = Handle::wrap_ptr(handle);
+
+        let mut flags = 0;
+        if unsafe { libbfio_handle_get_access_flags(self.as_type_ref(), &mut flags, &mut error) }
+            != 1
+        {
+            return Err(Error::try_from(error)?);
+        }
+
+        if unsafe { libbfio_handle_is_open(clone.as_type_ref(), &mut error) } != 1
+            && unsafe { libbfio_handle_open(clone.as_type_ref(), flags, &mut error) } != 1
+        {
+            return Err(Error::try_from(error)?);
+        }
+
+        clone.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+
+        Ok(clone)
+    }
+
+    /// Closes and re-opens the source with `flags`, keeping the current offset.
+    /// Re-opening never truncates the source, and doesn't create a missing file.
+    pub fn reopen(&self, flags: impl Into<AccessFlags>) -> Result<(), Error> {
+        let flags = flags.into().to_int();
+        let mut error = ptr::null_mut();
+
+        if unsafe { libbfio_handle_reopen(self.as_type_ref(), flags, &mut error) } != 1 {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// When set, the source is only open while it is being read,
+    /// so many handles can be kept around without exhausting file descriptors.
+    /// This is only supported for read-only handles.
+    pub fn set_open_on_demand(&self, open_on_demand: bool) -> Result<(), Error> {
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libbfio_handle_set_open_on_demand(self.as_type_ref(), open_on_demand as u8, &mut error)
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the source is currently open.
+    pub fn is_open(&self) -> Result<bool, Error> {
+        let mut error = ptr::null_mut();
+
+        match unsafe { libbfio_handle_is_open(self.as_type_ref(), &mut error) } {
+            1 => Ok(true),
+            0 => Ok(false),
+            _ => Err(Error::try_from(error)?),
+        }
+    }
+
+    /// Exposes `size` bytes of `inner`, starting at `offset`, as a read-only handle.
+    /// Offsets in the new handle are relative to the start of the window, and its size is `size`.
+    /// `inner` can be any seekable source, like another handle or an in-memory container.
+    pub fn open_window<R: Read + Seek + Send + 'static>(
+        mut inner: R,
+        offset: u64,
+        size: u64,
+    ) -> Result<Handle, Error> {
+        let source_size = inner.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+
+        match offset.checked_add(size) {
+            Some(end) if end <= source_size => {}
+            _ => {
+                return Err(Error::Other(format!(
+                    "A window of {} bytes at offset {} ends past the end of the source ({} bytes)",
+                    size, offset, source_size
+                )));
+            }
+        }
+
+        let io_handle = IoHandle::reader(Window::new(inner, offset, size));
+
+        Handle::from_io_handle(io_handle, LibbfioAccessFlags::Read.to_int())
+    }
+
+    /// The size of the source, as reported by the IO handle.
+    pub fn get_size(&self) -> Result<u64, Error> {
+        let mut size = 0;
+        let mut error = ptr::null_mut();
+
+        if unsafe { libbfio_handle_get_size(self.as_type_ref(), &mut size, &mut error) } != 1 {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(size)
+        }
+    }
+
+    /// Reads at `offset` without requiring a mutable handle.
+    /// Like the rest of libbfio, this also moves the current offset of the handle.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let offset = signed_offset(offset)?;
+        let mut error = ptr::null_mut();
+        let read_count = unsafe {
+            libbfio_handle_read_buffer_at_offset(
+                self.as_type_ref(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                offset,
+                &mut error,
+            )
         };
 
+        if !(error.is_null()) {
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
+        } else {
+            Ok(read_count as usize)
+        }
+    }
+
+    /// Creates a read-only handle over any seekable reader,
+    /// like an in-memory buffer or a decompressing reader.
+    pub fn from_reader<R: Read + Seek + Send + 'static>(reader: R) -> Result<Handle, Error> {
+        Handle::from_io_handle(IoHandle::reader(reader), LibbfioAccessFlags::Read.to_int())
+    }
+
+    /// Creates a handle which reads (and writes, depending on `flags`) through `inner`.
+    /// This is the read-write variant of `from_reader`.
+    pub fn from_stream(
+        inner: impl Read + Write + Seek + Send + 'static,
+        flags: impl Into<AccessFlags>,
+    ) -> Result<Handle, Error> {
+        Handle::from_io_handle(IoHandle::new(inner), flags.into().to_int())
+    }
+
+    /// Starts (or stops) recording which byte ranges of the source are read.
+    pub fn track_reads(&self, track_reads: bool) -> Result<(), Error> {
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libbfio_handle_set_track_offsets_read(self.as_type_ref(), track_reads as u8, &mut error)
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The byte ranges read since `track_reads` was enabled, with adjacent ranges merged.
+    /// For handles with a block cache, these exclude the pages read ahead by the cache.
+    pub fn read_ranges(&self) -> Result<Vec<Range<u64>>, Error> {
+        let mut number_of_ranges = 0;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libbfio_handle_get_number_of_offsets_read(
+                self.as_type_ref(),
+                &mut number_of_ranges,
+                &mut error,
+            )
+        } != 1
+        {
+            return Err(Error::try_from(error)?);
+        }
+
+        let mut ranges = Vec::with_capacity(number_of_ranges as usize);
+
+        for index in 0..number_of_ranges {
+            let mut offset = 0;
+            let mut size = 0;
+
+            if unsafe {
+                libbfio_handle_get_offset_read(
+                    self.as_type_ref(),
+                    index,
+                    &mut offset,
+                    &mut size,
+                    &mut error,
+                )
+            } != 1
+            {
+                return Err(Error::try_from(error)?);
+            }
+
+            ranges.push(offset..offset + size);
+        }
+
+        Ok(ranges)
+    }
+
+    /// Counters of the reads and seeks which reached the source of the handle
+    /// (the block cache, for handles which have one).
+    pub fn io_stats(&self) -> Result<IoStats, Error> {
+        self.io_handle().map(|io_handle| unsafe { (*io_handle).stats() })
+    }
+
+    /// The statistics of the block cache of handles opened with `open_file_cached` or `open_url`.
+    pub fn cache_stats(&self) -> Result<Option<CacheStats>, Error> {
+        self.io_handle().map(|io_handle| unsafe { (*io_handle).cache_stats() })
+    }
+
+    pub fn reset_io_stats(&self) -> Result<(), Error> {
+        self.io_handle().map(|io_handle| unsafe { (*io_handle).reset_stats() })
+    }
+
+    fn io_handle(&self) -> Result<*mut IoHandle, Error> {
+        let mut io_handle = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        if unsafe { libbfio_handle_get_io_handle(self.as_type_ref(), &mut io_handle, &mut error) }
+            != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(io_handle)
+        }
+    }
+
+    /// Creates a read-write handle over a copy-on-write overlay, which never writes to its source.
+    /// The overlay is shared with the handle, so it can still commit, discard or export the writes.
+    pub fn from_overlay<T: Read + Seek + Send + 'static>(
+        overlay: &Overlay<T>,
+    ) -> Result<Handle, Error> {
+        Handle::from_io_handle(IoHandle::new(overlay.clone()), AccessFlags::read_write().to_int())
+    }
+
+    fn from_io_handle(io_handle: IoHandle, flags: c_int) -> Result<Handle, Error> {
         let mut handle = ptr::null_mut();
         let mut error = ptr::null_mut();
 
-        let io_handle = IoHandle::file(f.map_err(|e| Error::FailedToOpenFile(e))?);
+        let is_writable = io_handle.is_writable();
+        // A re-openable source may be re-opened for writing later on.
+        let has_write_callback = is_writable || io_handle.can_be_reopened_for_writing();
+
+        if !is_writable && flags & LibbfioAccessFlags::Write.to_int() != 0 {
+            return Err(Error::Other(
+                "A read-only source can not be opened for writing".to_owned(),
+            ));
+        }
 
         // Allocate the fat pointer on the heap, because passing it over ffi boundary is lossy.
         let heap_ptr = Box::into_raw(Box::new(io_handle));
@@ -265,13 +587,18 @@ impl Handle {
                 &mut handle as _,
                 heap_ptr,
                 Some(io_handle_free),
-                None,
-                None,
-                None,
+                Some(io_handle_clone),
+                Some(io_handle_open),
+                Some(io_handle_close),
                 Some(io_handle_read),
-                Some(io_handle_write),
+                // Without a write callback, libbfio fails writes to read-only sources.
+                if has_write_callback {
+                    Some(io_handle_write)
+                } else {
+                    None
+                },
                 Some(io_handle_seek),
-                None,
+                Some(io_handle_exists),
                 Some(io_handle_is_open),
                 Some(io_handle_get_size),
                 // This will ensure that the library will try to free our inner handle
@@ -285,7 +612,7 @@ impl Handle {
             Err(Error::try_from(error)?)
         } else {
             let mut err = ptr::null_mut();
-            if unsafe { libbfio_handle_set_access_flags(handle, flags.to_int(), &mut err) } != 1 {
+            if unsafe { libbfio_handle_set_access_flags(handle, flags, &mut err) } != 1 {
                 return Err(Error::try_from(err)?);
             }
             Ok(Handle::wrap_ptr(handle))
@@ -301,17 +628,7 @@ impl Read for Handle {
         };
 
         if !(error.is_null()) {
-            let ffi_err = Error::try_from(error);
-
-            let io_err = match ffi_err {
-                Ok(e) => io::Error::new(io::ErrorKind::Other, format!("{}", e)),
-                Err(_e) => io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("error while getting error information"),
-                ),
-            };
-
-            Err(io_err)
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
         } else {
             Ok(read_count as usize)
         }
@@ -325,22 +642,12 @@ impl Write for Handle {
         if unsafe { libbfio_handle_get_access_flags(self.as_type_ref(), &mut flags, &mut error) }
             != 1
         {
-            let ffi_err = Error::try_from(error);
-
-            let io_err = match ffi_err {
-                Ok(e) => io::Error::new(io::ErrorKind::Other, format!("{}", e)),
-                Err(_e) => io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("error while getting error information"),
-                ),
-            };
-
-            return Err(io_err);
+            return Err(Error::try_from(error).unwrap_or_else(|e| e).into());
         }
 
         if flags & LibbfioAccessFlags::Write.to_int() == 0 {
             return Err(io::Error::new(
-                io::ErrorKind::Other,
+                io::ErrorKind::PermissionDenied,
                 format!("file is not open for writing"),
             ));
         };
@@ -351,17 +658,7 @@ impl Write for Handle {
         };
 
         if !(error.is_null()) {
-            let ffi_err = Error::try_from(error);
-
-            let io_err = match ffi_err {
-                Ok(e) => io::Error::new(io::ErrorKind::Other, format!("{}", e)),
-                Err(_e) => io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("error while getting error information"),
-                ),
-            };
-
-            Err(io_err)
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
         } else {
             Ok(write_count as usize)
         }
@@ -374,48 +671,147 @@ impl Write for Handle {
 
 impl Seek for Handle {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Current(p) => (p, SEEK_CUR),
+            SeekFrom::End(p) => (p, SEEK_END),
+            SeekFrom::Start(p) => (signed_offset(p)?, SEEK_SET),
+        };
+
         let mut error = ptr::null_mut();
         let seek_count = unsafe {
-            match pos {
-                SeekFrom::Current(p) => libbfio_handle_seek_offset(
-                    self.as_type_ref(),
-                    p as u64,
-                    SEEK_CUR as c_int,
-                    &mut error,
-                ),
-                SeekFrom::End(p) => libbfio_handle_seek_offset(
-                    self.as_type_ref(),
-                    p as u64,
-                    SEEK_END as c_int,
-                    &mut error,
-                ),
-                SeekFrom::Start(p) => libbfio_handle_seek_offset(
-                    self.as_type_ref(),
-                    p as u64,
-                    SEEK_SET as c_int,
-                    &mut error,
-                ),
-            }
+            libbfio_handle_seek_offset(self.as_type_ref(), offset, whence as c_int, &mut error)
         };
 
         if !(error.is_null()) {
-            let ffi_err = Error::try_from(error);
-
-            let io_err = match ffi_err {
-                Ok(e) => io::Error::new(io::ErrorKind::Other, format!("{}", e)),
-                Err(_e) => io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("error while getting error information"),
-                ),
-            };
-
-            Err(io_err)
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
         } else {
             Ok(seek_count as u64)
         }
     }
 }
 
+/// libbfio offsets are signed (`off64_t`), so larger offsets would be read as negative ones.
+fn signed_offset(offset: u64) -> io::Result<i64> {
+    if offset > i64::MAX as u64 {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("offset {} is too large", offset),
+        ))
+    } else {
+        Ok(offset as i64)
+    }
+}
+
+/// A handle which can never be written to, for sources which must not be modified (like evidence).
+pub struct ReadOnlyHandle(Handle);
+
+impl ReadOnlyHandle {
+    pub fn open_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let flags = AccessFlags::read_only();
+        let io_handle = IoHandle::path(path, flags).map_err(Error::FailedToOpenFile)?;
+
+        Handle::from_io_handle(io_handle.read_only(), flags.to_int()).map(ReadOnlyHandle)
+    }
+
+    pub fn from_reader<R: Read + Seek + Send + 'static>(reader: R) -> Result<Self, Error> {
+        Handle::from_reader(reader).map(ReadOnlyHandle)
+    }
+
+    /// The underlying handle, which still fails all writes.
+    pub fn into_handle(self) -> Handle {
+        self.0
+    }
+}
+
+impl Deref for ReadOnlyHandle {
+    type Target = Handle;
+
+    fn deref(&self) -> &Handle {
+        &self.0
+    }
+}
+
+impl From<ReadOnlyHandle> for Handle {
+    fn from(handle: ReadOnlyHandle) -> Handle {
+        handle.0
+    }
+}
+
+impl Read for ReadOnlyHandle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Seek for ReadOnlyHandle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+/// A handle which is open for both reading and writing.
+pub struct ReadWriteHandle(Handle);
+
+impl ReadWriteHandle {
+    pub fn open_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let flags = AccessFlags::read_write();
+        let io_handle = IoHandle::path(path, flags).map_err(Error::FailedToOpenFile)?;
+
+        Handle::from_io_handle(io_handle, flags.to_int()).map(ReadWriteHandle)
+    }
+
+    pub fn from_stream<RW: Read + Write + Seek + Send + 'static>(inner: RW) -> Result<Self, Error> {
+        Handle::from_io_handle(IoHandle::new(inner), AccessFlags::read_write().to_int())
+            .map(ReadWriteHandle)
+    }
+
+    pub fn into_handle(self) -> Handle {
+        self.0
+    }
+}
+
+impl Deref for ReadWriteHandle {
+    type Target = Handle;
+
+    fn deref(&self) -> &Handle {
+        &self.0
+    }
+}
+
+impl DerefMut for ReadWriteHandle {
+    fn deref_mut(&mut self) -> &mut Handle {
+        &mut self.0
+    }
+}
+
+impl From<ReadWriteHandle> for Handle {
+    fn from(handle: ReadWriteHandle) -> Handle {
+        handle.0
+    }
+}
+
+impl Read for ReadWriteHandle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for ReadWriteHandle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Seek for ReadWriteHandle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
 impl Drop for Handle {
     fn drop(&mut self) {
         use libyal_rs_common::ffi::AsTypeRef;
@@ -442,10 +838,14 @@ impl Drop for Handle {
 
 #[cfg(test)]
 mod tests {
-    use crate::handle::{Handle, LibbfioAccessFlags};
+    use crate::cache::CacheOptions;
+    use crate::handle::{
+        signed_offset, AccessFlags, Handle, LibbfioAccessFlags, ReadOnlyHandle, ReadWriteHandle,
+    };
+    use crate::overlay::Overlay;
 
     use std::fs::File;
-    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::io::{self, Read, Seek, SeekFrom, Write};
 
     use tempdir::TempDir;
 
@@ -484,6 +884,290 @@ mod tests {
         assert_eq!(buf, FILE_CONTENT);
     }
 
+    #[test]
+    fn test_from_stream() {
+        let stream = std::io::Cursor::new(FILE_CONTENT.to_vec());
+        let mut handle = Handle::from_stream(stream, LibbfioAccessFlags::Read).unwrap();
+        let mut buf = vec![];
+
+        handle.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, FILE_CONTENT);
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let reader = std::io::Cursor::new(FILE_CONTENT.to_vec());
+        let mut handle = Handle::from_reader(reader).unwrap();
+        let mut buf = [0; 4];
+
+        handle.seek(SeekFrom::Start(5)).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, b"data");
+        assert!(handle.write(b"more").is_err());
+    }
+
+    #[test]
+    fn test_read_only_handle_can_not_be_written() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let mut handle = ReadOnlyHandle::open_file(&test_file_path).unwrap();
+        let mut buf = vec![];
+        handle.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, FILE_CONTENT);
+
+        let mut handle = handle.into_handle();
+        handle.seek(SeekFrom::Start(0)).unwrap();
+        let err = handle.write(b"evil").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+        assert!(handle.reopen(LibbfioAccessFlags::Write).is_err());
+
+        assert_eq!(std::fs::read(&test_file_path).unwrap(), FILE_CONTENT);
+    }
+
+    #[test]
+    fn test_read_write_handle() {
+        let mut handle =
+            ReadWriteHandle::from_stream(std::io::Cursor::new(FILE_CONTENT.to_vec())).unwrap();
+        let mut buf = vec![];
+
+        handle.write_all(b"SOME").unwrap();
+        handle.seek(SeekFrom::Start(0)).unwrap();
+        handle.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, b"SOME_data");
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let mut handle = Handle::open_file(test_file_path, LibbfioAccessFlags::Read).unwrap();
+        handle.seek(SeekFrom::Start(5)).unwrap();
+
+        let mut clone = handle.try_clone().unwrap();
+        let mut buf = vec![];
+        clone.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, FILE_CONTENT);
+
+        let mut buf = vec![];
+        handle.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"data");
+    }
+
+    #[test]
+    fn test_from_bytes_can_be_cloned() {
+        let handle = Handle::from_bytes(FILE_CONTENT.to_vec()).unwrap();
+        let mut clone = handle.try_clone().unwrap();
+        let mut buf = vec![];
+
+        clone.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, FILE_CONTENT);
+    }
+
+    #[test]
+    fn test_reader_can_not_be_cloned() {
+        let handle = Handle::from_reader(std::io::Cursor::new(FILE_CONTENT.to_vec())).unwrap();
+
+        assert!(handle.try_clone().is_err());
+    }
+
+    #[test]
+    fn test_reopen() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let mut handle = Handle::open_file(&test_file_path, LibbfioAccessFlags::Read).unwrap();
+        assert!(handle.write(b"Hello").is_err());
+
+        handle.reopen(LibbfioAccessFlags::Write).unwrap();
+        handle.seek(SeekFrom::Start(0)).unwrap();
+        handle.write(b"Hello").unwrap();
+
+        assert_eq!(std::fs::read(&test_file_path).unwrap(), b"Hellodata");
+    }
+
+    #[test]
+    fn test_open_on_demand() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let mut handle = Handle::open_file(test_file_path, LibbfioAccessFlags::Read).unwrap();
+        handle.set_open_on_demand(true).unwrap();
+
+        let mut buf = [0; 4];
+        handle.seek(SeekFrom::Start(5)).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, b"data");
+        assert!(!handle.is_open().unwrap());
+    }
+
+    #[test]
+    fn test_read_ranges() {
+        let mut handle = Handle::from_bytes(FILE_CONTENT.to_vec()).unwrap();
+        let mut buf = [0; 2];
+
+        handle.track_reads(true).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+        handle.seek(SeekFrom::Start(5)).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+
+        assert_eq!(handle.read_ranges().unwrap(), vec![0..2, 5..9]);
+    }
+
+    #[test]
+    fn test_io_stats() {
+        let mut handle = Handle::from_bytes(FILE_CONTENT.to_vec()).unwrap();
+        let mut buf = [0; 4];
+
+        handle.reset_io_stats().unwrap();
+        handle.seek(SeekFrom::Start(5)).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+
+        let stats = handle.io_stats().unwrap();
+        assert_eq!(stats.bytes_read, 4);
+        assert_eq!(stats.reads, 1);
+        assert_eq!(stats.seeks, 1);
+    }
+
+    #[test]
+    fn test_open_file_cached() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let options = CacheOptions::new().page_size(4);
+        let mut handle =
+            Handle::open_file_cached(test_file_path, LibbfioAccessFlags::Read, &options).unwrap();
+        let mut buf = vec![];
+
+        handle.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, FILE_CONTENT);
+
+        handle.seek(SeekFrom::Start(1)).unwrap();
+        handle.read_to_end(&mut buf).unwrap();
+
+        let stats = handle.cache_stats().unwrap().unwrap();
+        assert!(stats.hits() > 0);
+        assert!(stats.misses() > 0);
+    }
+
+    #[test]
+    fn test_read_ranges_of_cached_handle_exclude_readahead() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let options = CacheOptions::new().page_size(2).readahead(2);
+        let mut handle =
+            Handle::open_file_cached(test_file_path, LibbfioAccessFlags::Read, &options).unwrap();
+        let mut buf = [0; 2];
+
+        handle.track_reads(true).unwrap();
+        handle.reset_io_stats().unwrap();
+
+        // The second (sequential) read also reads the next 2 pages ahead.
+        handle.read_exact(&mut buf).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+
+        assert_eq!(handle.read_ranges().unwrap(), vec![0..4]);
+        assert_eq!(handle.io_stats().unwrap().bytes_read, 4);
+        assert_eq!(handle.cache_stats().unwrap().unwrap().readahead_pages(), 2);
+    }
+
+    #[test]
+    fn test_open_mmap() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let mut handle = Handle::open_mmap(&test_file_path).unwrap();
+        let mut buf = vec![];
+
+        handle.seek(SeekFrom::Start(5)).unwrap();
+        handle.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, b"data");
+        assert!(handle.write(b"Hello").is_err());
+        assert!(handle.reopen(LibbfioAccessFlags::Write).is_err());
+        assert_eq!(handle.try_clone().unwrap().seek(SeekFrom::End(0)).unwrap(), 9);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_open_url() {
+        use crate::http::tests::{data, TestServer};
+        use crate::http::HttpOptions;
+
+        let url = TestServer::default().start();
+        let mut handle = Handle::open_url(&url, &HttpOptions::new()).unwrap();
+        let mut buf = vec![];
+
+        handle.seek(SeekFrom::Start(100)).unwrap();
+        handle.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, &data()[100..]);
+        assert!(handle.write(b"Hello").is_err());
+        assert!(handle.cache_stats().unwrap().unwrap().misses() > 0);
+        assert_eq!(handle.try_clone().unwrap().seek(SeekFrom::End(0)).unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_from_overlay() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let overlay = Overlay::new(File::open(&test_file_path).unwrap()).unwrap();
+        let mut handle = Handle::from_overlay(&overlay).unwrap();
+        let mut buf = vec![];
+
+        handle.write_all(b"SOME").unwrap();
+        handle.seek(SeekFrom::Start(0)).unwrap();
+        handle.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, b"SOME_data");
+        assert_eq!(overlay.modified_ranges(), vec![0..9]);
+        assert_eq!(std::fs::read(&test_file_path).unwrap(), FILE_CONTENT);
+    }
+
+    #[test]
+    fn test_read_at() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let handle = Handle::open_file(test_file_path, LibbfioAccessFlags::Read).unwrap();
+        let mut buf = [0; 4];
+
+        assert_eq!(handle.read_at(&mut buf, 5).unwrap(), 4);
+        assert_eq!(&buf, b"data");
+
+        // Would be a negative offset in libbfio.
+        let error = handle.read_at(&mut buf, i64::MAX as u64 + 1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_signed_offset() {
+        assert_eq!(signed_offset(i64::MAX as u64).unwrap(), i64::MAX);
+        assert_eq!(
+            signed_offset(u64::MAX).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
     #[test]
     fn test_write() {
         let tmp_dir = tmp_src_dir();
@@ -502,6 +1186,41 @@ mod tests {
         assert_eq!(buf, &[72, 101, 108, 108, 111, 100, 97, 116, 97]);
     }
 
+    #[test]
+    fn test_read_modify_write() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let mut handle = Handle::open_file(&test_file_path, AccessFlags::read_write()).unwrap();
+        let mut buf = [0; 4];
+
+        handle.read_exact(&mut buf).unwrap();
+        handle.seek(SeekFrom::Start(0)).unwrap();
+        handle.write_all(&buf.to_ascii_uppercase()).unwrap();
+
+        assert_eq!(std::fs::read(test_file_path).unwrap(), b"SOME_data");
+    }
+
+    #[test]
+    fn test_open_file_creates_and_truncates() {
+        let tmp_dir = tmp_src_dir();
+        let path = tmp_dir.path().join("new_file");
+        let flags = AccessFlags::read_write().create(true).truncate(true);
+
+        let mut handle = Handle::open_file(&path, flags).unwrap();
+        handle.write_all(FILE_CONTENT).unwrap();
+
+        // Clones re-open the file, which must not truncate it again.
+        let mut clone = handle.try_clone().unwrap();
+        let mut buf = vec![];
+        clone.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, FILE_CONTENT);
+        assert!(Handle::open_file(tmp_dir.path().join("missing"), AccessFlags::read_write())
+            .is_err());
+    }
+
     #[test]
     fn test_write_checks_access_flags() {
         let tmp_dir = tmp_src_dir();
@@ -512,6 +1231,47 @@ mod tests {
         assert!(handle.write(b"Hello").is_err());
     }
 
+    #[test]
+    fn test_open_window() {
+        let tmp_dir = tmp_src_dir();
+        let test_file = test_file(&tmp_dir, Some(FILE_CONTENT));
+        let test_file_path = tmp_dir.path().join(test_file).canonicalize().unwrap();
+
+        let inner = Handle::open_file(test_file_path, LibbfioAccessFlags::Read).unwrap();
+        let mut handle = Handle::open_window(inner, 5, 3).unwrap();
+        let mut buf = vec![];
+
+        handle.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, &FILE_CONTENT[5..8]);
+
+        assert_eq!(handle.seek(SeekFrom::End(0)).unwrap(), 3);
+        assert!(handle.write(b"Hello").is_err());
+    }
+
+    #[test]
+    fn test_open_window_over_reader() {
+        let reader = std::io::Cursor::new(FILE_CONTENT.to_vec());
+        let mut handle = Handle::open_window(reader, 2, 5).unwrap();
+        let mut buf = [0; 3];
+
+        assert_eq!(handle.get_size().unwrap(), 5);
+
+        handle.seek(SeekFrom::End(-3)).unwrap();
+        handle.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"_da");
+        assert_eq!(handle.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_open_window_past_the_end_fails() {
+        let reader = || std::io::Cursor::new(FILE_CONTENT.to_vec());
+
+        assert!(Handle::open_window(reader(), 5, 5).is_err());
+        assert!(Handle::open_window(reader(), 10, 0).is_err());
+        assert!(Handle::open_window(reader(), u64::MAX, 2).is_err());
+        assert!(Handle::open_window(reader(), 9, 0).is_ok());
+    }
+
     #[test]
     fn test_seek() {
         let tmp_dir = tmp_src_dir();
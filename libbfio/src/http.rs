@@ -0,0 +1,614 @@
+//! A read-only IO source over HTTP, which reads with `Range` requests.
+//!
+//! The size is learned from a `HEAD` request, or from the `Content-Range` of a one byte request
+//! when the server doesn't answer `HEAD` with a `Content-Length`.
+//! Failed requests (transport errors, `408`, `429` and `5xx` responses) are retried with
+//! an exponential backoff. Handles put a block cache in front of the source, since every
+//! read which reaches it is a round trip.
+use crate::cache::CacheOptions;
+use crate::seek::seek_position;
+use log::warn;
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::thread;
+use std::time::Duration;
+
+/// Credentials which are sent with every request.
+#[derive(Clone)]
+pub enum Auth {
+    Basic { user: String, password: String },
+    Bearer(String),
+}
+
+impl Auth {
+    fn header(&self) -> String {
+        match self {
+            Auth::Basic { user, password } => {
+                format!("Basic {}", base64::encode(format!("{}:{}", user, password)))
+            }
+            Auth::Bearer(token) => format!("Bearer {}", token),
+        }
+    }
+}
+
+// Credentials are left out, so options can be logged.
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Basic { user, .. } => write!(f, "Basic {{ user: {:?}, .. }}", user),
+            Auth::Bearer(_) => write!(f, "Bearer(..)"),
+        }
+    }
+}
+
+/// Options of an `HttpReader`, and of the block cache of handles opened with `open_url`.
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    auth: Option<Auth>,
+    retries: u32,
+    backoff: Duration,
+    timeout: Duration,
+    cache: CacheOptions,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            auth: None,
+            retries: 3,
+            backoff: Duration::from_millis(200),
+            timeout: Duration::from_secs(30),
+            cache: CacheOptions::new().page_size(256 * 1024).capacity(64),
+        }
+    }
+}
+
+impl HttpOptions {
+    pub fn new() -> Self {
+        HttpOptions::default()
+    }
+
+    pub fn basic_auth(mut self, user: impl Into<String>, password: impl Into<String>) -> Self {
+        self.auth = Some(Auth::Basic {
+            user: user.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+        self.auth = Some(Auth::Bearer(token.into()));
+        self
+    }
+
+    /// The number of times a failed request is retried. Defaults to 3.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// The delay before the first retry, which doubles with every retry. Defaults to 200ms.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// The timeout of a single request. Defaults to 30s.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The block cache of handles. Defaults to 64 pages of 256 KiB.
+    pub fn cache(mut self, cache: CacheOptions) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub(crate) fn cache_options(&self) -> &CacheOptions {
+        &self.cache
+    }
+}
+
+/// Cloning is cheap, and the clone shares the connection pool but has a position of its own.
+#[derive(Clone)]
+pub struct HttpReader {
+    agent: ureq::Agent,
+    url: String,
+    options: HttpOptions,
+    len: u64,
+    position: u64,
+}
+
+/// The outcome of a failed attempt at a request.
+enum Failure {
+    Retryable(io::Error),
+    Fatal(io::Error),
+}
+
+impl HttpReader {
+    pub fn open(url: impl Into<String>, options: HttpOptions) -> io::Result<Self> {
+        let agent = ureq::AgentBuilder::new().timeout(options.timeout).build();
+
+        let mut reader = HttpReader {
+            agent,
+            url: url.into(),
+            options,
+            len: 0,
+            position: 0,
+        };
+
+        reader.len = reader.fetch_len()?;
+
+        Ok(reader)
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn request(&self, method: &str) -> ureq::Request {
+        let request = self.agent.request(method, &self.url);
+
+        match &self.options.auth {
+            Some(auth) => request.set("Authorization", &auth.header()),
+            None => request,
+        }
+    }
+
+    /// Calls `attempt` until it succeeds, fails with a fatal error or runs out of retries.
+    fn with_retries<T>(&self, mut attempt: impl FnMut() -> Result<T, Failure>) -> io::Result<T> {
+        let mut backoff = self.options.backoff;
+        let mut retry = 0;
+
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(Failure::Fatal(e)) => return Err(e),
+                Err(Failure::Retryable(e)) if retry >= self.options.retries => return Err(e),
+                Err(Failure::Retryable(e)) => {
+                    retry += 1;
+                    warn!(
+                        "Retrying a request to {} in {:?} ({}/{}): {}",
+                        redacted(&self.url),
+                        backoff,
+                        retry,
+                        self.options.retries,
+                        e
+                    );
+
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+
+    fn fetch_len(&self) -> io::Result<u64> {
+        let content_length = self.with_retries(|| {
+            match self.request("HEAD").call() {
+                Ok(response) => Ok(response
+                    .header("Content-Length")
+                    .and_then(|length| length.trim().parse().ok())),
+                // Some servers (and presigned URLs) only allow `GET`.
+                Err(ureq::Error::Status(status, _)) if status == 403 || status == 405 => Ok(None),
+                Err(e) => Err(self.failure("HEAD", e)),
+            }
+        })?;
+
+        if let Some(len) = content_length {
+            return Ok(len);
+        }
+
+        self.with_retries(|| {
+            let response = self
+                .request("GET")
+                .set("Range", "bytes=0-0")
+                .call()
+                .map_err(|e| self.failure("GET", e))?;
+
+            if response.status() != 206 {
+                return Err(Failure::Fatal(not_ranged(&self.url)));
+            }
+
+            response
+                .header("Content-Range")
+                .and_then(parse_content_range)
+                .map(|(_, len)| len)
+                .ok_or_else(|| {
+                    Failure::Fatal(invalid_data(format!(
+                        "{} answered without the size in its Content-Range",
+                        redacted(&self.url)
+                    )))
+                })
+        })
+    }
+
+    /// Reads the bytes at `offset` into `buf`, which must not go past the end.
+    fn read_range(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let range = format!("bytes={}-{}", offset, offset + buf.len() as u64 - 1);
+
+        self.with_retries(|| {
+            let response = self
+                .request("GET")
+                .set("Range", &range)
+                .call()
+                .map_err(|e| self.failure("GET", e))?;
+
+            if response.status() != 206 {
+                return Err(Failure::Fatal(not_ranged(&self.url)));
+            }
+
+            match response
+                .header("Content-Range")
+                .and_then(parse_content_range)
+            {
+                Some((start, _)) if start == offset => {}
+                _ => {
+                    return Err(Failure::Fatal(invalid_data(format!(
+                        "{} answered {} with another range",
+                        redacted(&self.url),
+                        range
+                    ))))
+                }
+            }
+
+            // A connection which drops in the middle of the body is worth another try.
+            response
+                .into_reader()
+                .read_exact(buf)
+                .map_err(Failure::Retryable)
+        })
+    }
+
+    fn failure(&self, method: &str, error: ureq::Error) -> Failure {
+        match error {
+            ureq::Error::Status(status, response) => {
+                let e = io::Error::new(
+                    status_kind(status),
+                    format!(
+                        "{} {} failed with {} {}",
+                        method,
+                        redacted(&self.url),
+                        status,
+                        response.status_text()
+                    ),
+                );
+
+                if status == 408 || status == 429 || status >= 500 {
+                    Failure::Retryable(e)
+                } else {
+                    Failure::Fatal(e)
+                }
+            }
+            ureq::Error::Transport(transport) => Failure::Retryable(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "{} {} failed: {}",
+                    method,
+                    redacted(&self.url),
+                    describe(&transport)
+                ),
+            )),
+        }
+    }
+}
+
+fn status_kind(status: u16) -> io::ErrorKind {
+    match status {
+        401 | 403 => io::ErrorKind::PermissionDenied,
+        404 | 410 => io::ErrorKind::NotFound,
+        408 => io::ErrorKind::TimedOut,
+        _ => io::ErrorKind::Other,
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The error without the URL, which the `Display` of transport errors starts with.
+fn describe(transport: &ureq::Transport) -> String {
+    let mut description = transport.kind().to_string();
+
+    if let Some(message) = transport.message() {
+        description.push_str(": ");
+        description.push_str(message);
+    }
+    if let Some(source) = std::error::Error::source(transport) {
+        description.push_str(": ");
+        description.push_str(&source.to_string());
+    }
+
+    description
+}
+
+fn not_ranged(url: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("{} does not support range requests", redacted(url)),
+    )
+}
+
+/// The URL without its query string, which is the credential of presigned URLs,
+/// so it can be logged and put in errors.
+fn redacted(url: &str) -> String {
+    match url.find('?') {
+        Some(query) => format!("{}?..", &url[..query]),
+        None => url.to_owned(),
+    }
+}
+
+/// The first offset and the full size in a `Content-Range` like `bytes 0-99/1000`.
+fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (range, len) = range.split_at(range.find('/')?);
+    let start = range.split('-').next()?;
+
+    Some((start.trim().parse().ok()?, len[1..].trim().parse().ok()?))
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = (buf.len() as u64).min(self.len.saturating_sub(self.position)) as usize;
+
+        if count == 0 {
+            return Ok(0);
+        }
+
+        self.read_range(self.position, &mut buf[..count])?;
+        self.position += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.len, pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::cache::BlockCache;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    pub(crate) fn data() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// A minimal HTTP server for `data()`, which answers a request per connection.
+    #[derive(Clone, Default)]
+    pub(crate) struct TestServer {
+        /// The `Authorization` header which requests must have.
+        pub(crate) authorization: Option<String>,
+        /// Answers `HEAD` requests with a `405`.
+        pub(crate) no_head: bool,
+        /// The number of requests which are answered with a `503` first.
+        pub(crate) failures: Arc<AtomicUsize>,
+        /// The number of requests so far.
+        pub(crate) requests: Arc<AtomicUsize>,
+    }
+
+    impl TestServer {
+        /// Serves on a free local port, and returns the URL of the data.
+        pub(crate) fn start(&self) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/image.dd", listener.local_addr().unwrap());
+            let server = self.clone();
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    server.answer(stream.unwrap());
+                }
+            });
+
+            url
+        }
+
+        fn answer(&self, mut stream: TcpStream) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            let mut headers = vec![];
+
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_owned());
+            }
+
+            self.requests.fetch_add(1, Ordering::SeqCst);
+
+            let header = |name: &str| {
+                headers.iter().find_map(|header| {
+                    let (key, value) = header.split_at(header.find(':')?);
+                    if key.eq_ignore_ascii_case(name) {
+                        Some(value[1..].trim().to_owned())
+                    } else {
+                        None
+                    }
+                })
+            };
+
+            let data = data();
+            let is_head = request_line.starts_with("HEAD");
+
+            let (status, extra_headers, body) = if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                ("503 Service Unavailable", String::new(), vec![])
+            } else if self.authorization.is_some() && header("Authorization") != self.authorization
+            {
+                ("401 Unauthorized", String::new(), vec![])
+            } else if is_head && self.no_head {
+                ("405 Method Not Allowed", String::new(), vec![])
+            } else if let Some(range) = header("Range") {
+                let range = range.trim_start_matches("bytes=");
+                let (start, end) = range.split_at(range.find('-').unwrap());
+                let start: usize = start.parse().unwrap();
+                let end: usize = end[1..].parse::<usize>().unwrap().min(data.len() - 1);
+
+                (
+                    "206 Partial Content",
+                    format!("Content-Range: bytes {}-{}/{}\r\n", start, end, data.len()),
+                    data[start..=end].to_vec(),
+                )
+            } else {
+                ("200 OK", String::new(), data)
+            };
+
+            let mut response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n",
+                status,
+                body.len(),
+                extra_headers
+            )
+            .into_bytes();
+
+            if !is_head {
+                response.extend(body);
+            }
+
+            let _ = stream.write_all(&response);
+        }
+    }
+
+    fn options() -> HttpOptions {
+        HttpOptions::new().backoff(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn test_read_ranges() {
+        let url = TestServer::default().start();
+        let mut reader = HttpReader::open(url, options()).unwrap();
+        let mut buf = [0; 10];
+
+        assert_eq!(reader.len(), 1000);
+
+        reader.seek(SeekFrom::Start(500)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &data()[500..510]);
+
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], &data()[996..]);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_len_from_content_range() {
+        let server = TestServer {
+            no_head: true,
+            ..TestServer::default()
+        };
+
+        let reader = HttpReader::open(server.start(), options()).unwrap();
+
+        assert_eq!(reader.len(), 1000);
+    }
+
+    #[test]
+    fn test_failed_requests_are_retried() {
+        let server = TestServer::default();
+        let url = server.start();
+
+        server.failures.store(2, Ordering::SeqCst);
+        let mut reader = HttpReader::open(url.as_str(), options()).unwrap();
+        assert_eq!(server.requests.load(Ordering::SeqCst), 3);
+
+        server.failures.store(2, Ordering::SeqCst);
+        let mut buf = [0; 10];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &data()[..10]);
+
+        server.failures.store(2, Ordering::SeqCst);
+        let err = HttpReader::open(url, options().retries(1)).err().unwrap();
+        assert!(err.to_string().contains("503"));
+    }
+
+    #[test]
+    fn test_auth() {
+        let basic = TestServer {
+            authorization: Some(format!("Basic {}", base64::encode("user:password"))),
+            ..TestServer::default()
+        };
+        let url = basic.start();
+
+        let err = HttpReader::open(url.as_str(), options()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        // Rejected credentials aren't retried.
+        assert_eq!(basic.requests.load(Ordering::SeqCst), 1);
+
+        assert!(HttpReader::open(url, options().basic_auth("user", "password")).is_ok());
+
+        let bearer = TestServer {
+            authorization: Some("Bearer token".to_owned()),
+            ..TestServer::default()
+        };
+        assert!(HttpReader::open(bearer.start(), options().bearer_auth("token")).is_ok());
+    }
+
+    #[test]
+    fn test_query_string_is_redacted() {
+        assert_eq!(
+            redacted("https://bucket.s3.amazonaws.com/image.dd?X-Amz-Signature=secret"),
+            "https://bucket.s3.amazonaws.com/image.dd?.."
+        );
+        assert_eq!(redacted("http://host/image.dd"), "http://host/image.dd");
+
+        let bearer = TestServer {
+            authorization: Some("Bearer token".to_owned()),
+            ..TestServer::default()
+        };
+        let url = format!("{}?signature=secret", bearer.start());
+        let err = HttpReader::open(url, options()).err().unwrap();
+        assert!(!err.to_string().contains("secret"));
+
+        // Nothing listens on a port which was just freed.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{}/image.dd?signature=secret", port);
+        let err = HttpReader::open(url, options().retries(0)).err().unwrap();
+        assert!(err.to_string().contains("image.dd?.."));
+        assert!(!err.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn test_cached_reads_need_few_requests() {
+        let server = TestServer::default();
+        let reader = HttpReader::open(server.start(), options()).unwrap();
+        let cache_options = CacheOptions::new().page_size(100).readahead(4);
+        let mut cache = BlockCache::new(reader, cache_options);
+        let mut buf = vec![];
+
+        for _ in 0..1000 {
+            let mut byte = [0; 1];
+            cache.read_exact(&mut byte).unwrap();
+            buf.push(byte[0]);
+        }
+
+        assert_eq!(buf, data());
+        // A HEAD, then a first page and two reads of five pages.
+        assert_eq!(server.requests.load(Ordering::SeqCst), 4);
+    }
+}
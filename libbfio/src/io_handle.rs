@@ -1,45 +1,488 @@
+use crate::access::AccessFlags;
+use crate::cache::{BlockCache, CacheOptions, CacheStats};
 use crate::error::Error;
+#[cfg(feature = "http")]
+use crate::http::{HttpOptions, HttpReader};
+use crate::mmap::MmapReader;
+use crate::segments::SegmentedReader;
 use crate::ffi_error::LibbfioErrorRefMut;
 use libbfio_sys::{size64_t, SEEK_CUR, SEEK_END, SEEK_SET};
 use log::trace;
 
 use libcerror_sys::*;
+use std::any::Any;
+use std::convert::TryFrom;
 use std::ffi::CString;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::os::raw::c_int;
-use std::path::Path;
-use std::slice;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::{ptr, slice};
+use std::sync::Arc;
 
-pub trait RwSeek: Read + Write + Seek {}
-impl<T: Read + Write + Seek> RwSeek for T {}
+/// IO sources must be `Send`, so a `Handle` can be moved to another thread.
+pub trait RwSeek: Read + Write + Seek + Send {}
+impl<T: Read + Write + Seek + Send> RwSeek for T {}
+
+/// A read-only IO source.
+pub trait ReadSeek: Read + Seek + Send {}
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+enum Source {
+    ReadOnly(Box<dyn ReadSeek>),
+    ReadWrite(Box<dyn RwSeek>),
+}
+
+impl Source {
+    fn new(inner: impl RwSeek + 'static, writable: bool) -> Self {
+        if writable {
+            Source::ReadWrite(Box::new(inner))
+        } else {
+            Source::ReadOnly(Box::new(inner))
+        }
+    }
+}
+
+/// Re-creates the source of an IO handle from its access flags.
+/// This is what allows libbfio to close, re-open and clone the handle.
+#[derive(Clone)]
+struct Opener {
+    /// The file the source is read from, if any, checked by the `exists` callback.
+    path: Option<PathBuf>,
+    /// Shared by all the caches of the re-opened (and cloned) sources.
+    cache_stats: Option<CacheStats>,
+    open: Arc<dyn Fn(AccessFlags) -> io::Result<Source> + Send + Sync>,
+}
 
 pub struct IoHandle {
-    inner: Box<dyn RwSeek>,
+    /// `None` while a re-openable handle is closed.
+    inner: Option<Source>,
+    opener: Option<Opener>,
+    /// Whether re-opening the source for writing is refused.
+    read_only: bool,
     is_open: bool,
+    stats: IoStats,
+}
+
+/// Counters of the requests which reached the source of a handle.
+/// For handles with a block cache, these are the requests to the cache, not to what it caches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStats {
+    pub bytes_read: u64,
+    pub reads: u64,
+    pub seeks: u64,
 }
 
 impl IoHandle {
+    pub fn new(inner: impl RwSeek + 'static) -> Self {
+        IoHandle {
+            inner: Some(Source::ReadWrite(Box::new(inner))),
+            opener: None,
+            read_only: false,
+            is_open: true,
+            stats: IoStats::default(),
+        }
+    }
+
     pub fn file(inner: File) -> Self {
+        IoHandle::new(inner)
+    }
+
+    /// An IO handle over the file at `path`, which is re-opened by path when libbfio
+    /// re-opens or clones the handle.
+    pub fn path(path: impl AsRef<Path>, access_flags: AccessFlags) -> io::Result<Self> {
+        IoHandle::file_path(path.as_ref(), access_flags, None)
+    }
+
+    /// Like `path`, but reads go through a block cache.
+    /// The cached pages are dropped when the handle is closed.
+    pub fn cached_path(
+        path: impl AsRef<Path>,
+        access_flags: AccessFlags,
+        options: &CacheOptions,
+    ) -> io::Result<Self> {
+        IoHandle::file_path(path.as_ref(), access_flags, Some(options.clone()))
+    }
+
+    fn file_path(
+        path: &Path,
+        access_flags: AccessFlags,
+        cache: Option<CacheOptions>,
+    ) -> io::Result<Self> {
+        let file_path = path.to_path_buf();
+        let cache_stats = cache.as_ref().map(|_| CacheStats::new());
+        let stats = cache_stats.clone();
+
+        let open = move |access_flags: AccessFlags| {
+            let file = access_flags.open_options().open(&file_path)?;
+            let writable = access_flags.is_writable();
+
+            Ok(match (&cache, &stats) {
+                (Some(options), Some(stats)) => Source::new(
+                    BlockCache::with_stats(file, options.clone(), stats.clone()),
+                    writable,
+                ),
+                _ => Source::new(file, writable),
+            })
+        };
+
+        IoHandle::reopenable(Some(path.to_path_buf()), cache_stats, open, access_flags)
+    }
+
+    /// An IO handle over a read-only memory map of the file at `path`.
+    pub fn mmap(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let map_path = path.clone();
+
+        let open = move |_| Ok(Source::ReadOnly(Box::new(MmapReader::open(&map_path)?)));
+
+        IoHandle::reopenable(Some(path), None, open, AccessFlags::read_only())
+            .map(IoHandle::read_only)
+    }
+
+    /// An IO handle over a split image, made of the segments at `paths`.
+    pub fn segments(paths: Vec<PathBuf>) -> io::Result<Self> {
+        let first_segment = paths.first().cloned();
+
+        let open = move |_| {
+            let reader = SegmentedReader::from_paths(paths.clone())?;
+            Ok(Source::ReadOnly(Box::new(reader)))
+        };
+
+        IoHandle::reopenable(first_segment, None, open, AccessFlags::read_only())
+            .map(IoHandle::read_only)
+    }
+
+    /// An IO handle over the resource at `url`, read with `Range` requests through a block cache.
+    /// Clones share the connection pool, but each has a cache of its own.
+    #[cfg(feature = "http")]
+    pub fn url(url: &str, options: &HttpOptions) -> io::Result<Self> {
+        let reader = HttpReader::open(url, options.clone())?;
+        let cache = options.cache_options().clone();
+        let cache_stats = CacheStats::new();
+        let stats = cache_stats.clone();
+
+        let open = move |_| {
+            let cached = BlockCache::with_stats(reader.clone(), cache.clone(), stats.clone());
+            Ok(Source::ReadOnly(Box::new(cached)))
+        };
+
+        IoHandle::reopenable(None, Some(cache_stats), open, AccessFlags::read_only())
+            .map(IoHandle::read_only)
+    }
+
+    /// An IO handle over shared, in-memory data.
+    /// Clones share the data but each has a position of its own.
+    pub fn bytes(data: Arc<[u8]>) -> Self {
+        let open = move |_| Ok(Source::ReadOnly(Box::new(Cursor::new(data.clone()))));
+
+        IoHandle::reopenable(None, None, open, AccessFlags::read_only())
+            .expect("opening in-memory data can not fail")
+    }
+
+    fn reopenable(
+        path: Option<PathBuf>,
+        cache_stats: Option<CacheStats>,
+        open: impl Fn(AccessFlags) -> io::Result<Source> + Send + Sync + 'static,
+        access_flags: AccessFlags,
+    ) -> io::Result<Self> {
+        let inner = open(access_flags)?;
+
+        Ok(IoHandle {
+            inner: Some(inner),
+            opener: Some(Opener {
+                path,
+                cache_stats,
+                open: Arc::new(open),
+            }),
+            read_only: false,
+            is_open: true,
+            stats: IoStats::default(),
+        })
+    }
+
+    /// An IO handle over a read-only source.
+    /// libbfio gets no write callback for it, so writing through the handle fails.
+    pub fn reader(inner: impl ReadSeek + 'static) -> Self {
         IoHandle {
-            inner: Box::new(inner) as Box<dyn RwSeek>,
-            is_open: true
+            inner: Some(Source::ReadOnly(Box::new(inner))),
+            opener: None,
+            read_only: false,
+            is_open: true,
+            stats: IoStats::default(),
+        }
+    }
+
+    /// Refuses to re-open the source for writing.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn is_writable(&self) -> bool {
+        match self.inner {
+            Some(Source::ReadOnly(_)) => false,
+            Some(Source::ReadWrite(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Whether the source may be re-opened for writing.
+    pub fn can_be_reopened_for_writing(&self) -> bool {
+        self.opener.is_some() && !self.read_only
+    }
+
+    fn source(&mut self) -> io::Result<&mut Source> {
+        match self.inner.as_mut() {
+            Some(source) if self.is_open => Ok(source),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "the IO handle is closed",
+            )),
+        }
+    }
+
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.opener.as_ref().and_then(|opener| opener.cache_stats.clone())
+    }
+
+    pub fn stats(&self) -> IoStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = IoStats::default();
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = match self.source()? {
+            Source::ReadOnly(inner) => inner.read(buf),
+            Source::ReadWrite(inner) => inner.read(buf),
+        }?;
+
+        self.stats.reads += 1;
+        self.stats.bytes_read += count as u64;
+
+        Ok(count)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.source()? {
+            Source::ReadOnly(_) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the source is read-only",
+            )),
+            Source::ReadWrite(inner) => inner.write(buf),
+        }
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.stats.seeks += 1;
+
+        match self.source()? {
+            Source::ReadOnly(inner) => inner.seek(pos),
+            Source::ReadWrite(inner) => inner.seek(pos),
+        }
+    }
+
+    fn stream_len(&mut self) -> io::Result<u64> {
+        match self.source()? {
+            Source::ReadOnly(inner) => inner.stream_len(),
+            Source::ReadWrite(inner) => inner.stream_len(),
+        }
+    }
+
+    /// Opens the source again if it was closed.
+    /// Sources which can't be re-created are never really closed, so they are only marked open.
+    fn open(&mut self, access_flags: c_int) -> io::Result<()> {
+        let access_flags = AccessFlags::from_int(access_flags);
+
+        if self.read_only && access_flags.is_writable() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the source is read-only",
+            ));
+        }
+
+        if self.inner.is_none() {
+            let opener = self.opener.as_ref().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::Other, "the IO handle can not be re-opened")
+            })?;
+
+            // Re-opening (for a clone, or on demand) must not truncate what was written so far.
+            self.inner = Some((opener.open)(access_flags.truncate(false))?);
+        }
+
+        self.is_open = true;
+
+        Ok(())
+    }
+
+    /// Drops a re-openable source, which releases its file descriptor.
+    fn close(&mut self) {
+        if self.opener.is_some() {
+            self.inner = None;
+        }
+
+        self.is_open = false;
+    }
+
+    /// A closed IO handle over the same source, which libbfio opens when needed.
+    fn try_clone(&self) -> io::Result<IoHandle> {
+        match &self.opener {
+            Some(opener) => Ok(IoHandle {
+                inner: None,
+                opener: Some(opener.clone()),
+                read_only: self.read_only,
+                is_open: false,
+                stats: IoStats::default(),
+            }),
+            None => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the IO handle can not be cloned",
+            )),
+        }
+    }
+
+    fn exists(&self) -> bool {
+        match self.opener.as_ref().and_then(|opener| opener.path.as_ref()) {
+            Some(path) => path.exists(),
+            None => true,
         }
     }
 }
 
 pub const IO_ERR: i32 = LIBCERROR_ERROR_DOMAINS_LIBCERROR_ERROR_DOMAIN_IO as i32;
 pub const ARGUMENT_ERR: i32 = LIBCERROR_ERROR_DOMAINS_LIBCERROR_ERROR_DOMAIN_ARGUMENTS as i32;
+pub const RUNTIME_ERR: i32 = LIBCERROR_ERROR_DOMAINS_LIBCERROR_ERROR_DOMAIN_RUNTIME as i32;
+
+/// libcerror formats the message of a callback error as an argument, so it is used verbatim.
+const MESSAGE_FORMAT: &[u8] = b"%s.\0";
+
+/// An error of a callback, which is handed to libbfio as a libcerror error.
+struct CallbackError {
+    domain: i32,
+    code: u32,
+    message: String,
+}
+
+impl CallbackError {
+    fn new(domain: i32, code: u32, message: impl Into<String>) -> Self {
+        CallbackError {
+            domain,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn argument(code: u32, message: &str) -> Self {
+        CallbackError::new(ARGUMENT_ERR, code, message)
+    }
+
+    fn io(code: u32, e: io::Error) -> Self {
+        CallbackError::new(IO_ERR, code, e.to_string())
+    }
+
+    fn panic(payload: Box<dyn Any + Send>) -> Self {
+        let reason = match payload.downcast::<String>() {
+            Ok(reason) => *reason,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(reason) => (*reason).to_owned(),
+                Err(_) => "unknown reason".to_owned(),
+            },
+        };
+
+        CallbackError::new(
+            RUNTIME_ERR,
+            LIBCERROR_RUNTIME_ERROR_LIBCERROR_RUNTIME_ERROR_GENERIC,
+            format!("panicked: {}", reason),
+        )
+    }
+}
+
+/// Runs the body of the callback `name`, setting `error` and returning `failure` if it fails.
+///
+/// Panics are caught, since unwinding into C is undefined behaviour.
+unsafe fn callback<T>(
+    name: &str,
+    error: *mut LibbfioErrorRefMut,
+    failure: T,
+    body: impl FnOnce() -> Result<T, CallbackError>,
+) -> T {
+    trace!("{}", name);
+
+    let e = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => e,
+        Err(payload) => CallbackError::panic(payload),
+    };
+
+    // libcerror copies the message, so it is freed when it goes out of scope.
+    // Nul bytes (say, from a path) would truncate it, so they are escaped.
+    let message = CString::new(format!("{}: {}", name, e.message).replace('\0', "\\0"))
+        .unwrap_or_default();
+
+    libcerror_error_set(
+        error as _,
+        e.domain,
+        e.code as i32,
+        MESSAGE_FORMAT.as_ptr() as *const c_char,
+        message.as_ptr(),
+    );
+
+    failure
+}
+
+unsafe fn io_handle_mut<'a>(io_handle: *mut IoHandle) -> Result<&'a mut IoHandle, CallbackError> {
+    io_handle.as_mut().ok_or_else(|| {
+        CallbackError::argument(
+            LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_INVALID_VALUE,
+            "invalid IO handle",
+        )
+    })
+}
+
+/// Checks a buffer passed by libbfio, which must be valid for `size` bytes.
+fn check_buffer(buffer: *const u8, size: usize) -> Result<(), CallbackError> {
+    if buffer.is_null() {
+        return Err(CallbackError::argument(
+            LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_INVALID_VALUE,
+            "invalid buffer",
+        ));
+    }
+
+    // The count is returned as an `isize`.
+    if size > isize::MAX as usize {
+        return Err(CallbackError::argument(
+            LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_VALUE_EXCEEDS_MAXIMUM,
+            "invalid size value exceeds maximum",
+        ));
+    }
+
+    Ok(())
+}
 
 #[no_mangle]
 pub unsafe extern "C" fn io_handle_free(
     io_handle: *mut *mut IoHandle,
-    _error: *mut LibbfioErrorRefMut,
+    error: *mut LibbfioErrorRefMut,
 ) -> c_int {
-    trace!("io_handle_free");
-    Box::from_raw(*io_handle);
+    callback("io_handle_free", error, -1, || {
+        if io_handle.is_null() {
+            return Err(CallbackError::argument(
+                LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_INVALID_VALUE,
+                "invalid IO handle",
+            ));
+        }
 
-    1 as c_int
+        if !(*io_handle).is_null() {
+            drop(Box::from_raw(*io_handle));
+            *io_handle = ptr::null_mut();
+        }
+
+        Ok(1)
+    })
 }
 
 #[no_mangle]
@@ -49,24 +492,15 @@ pub unsafe extern "C" fn io_handle_read(
     size: usize,
     error: *mut LibbfioErrorRefMut,
 ) -> isize {
-    trace!("io_handle_read");
+    callback("io_handle_read", error, -1, || {
+        let io_handle = io_handle_mut(io_handle)?;
+        check_buffer(buffer, size)?;
 
-    let s = slice::from_raw_parts_mut(buffer, size);
-    match (*io_handle).inner.read(s) {
-        Ok(cnt) => cnt as isize,
-        Err(e) => {
-            libcerror_error_set(
-                error as _,
-                IO_ERR,
-                LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_READ_FAILED as i32,
-                CString::new("%s.").unwrap().into_raw(),
-                CString::new(format!("io_handle_read: {:?}", e))
-                    .unwrap()
-                    .into_raw(),
-            );
-            return -1;
-        }
-    }
+        io_handle
+            .read(slice::from_raw_parts_mut(buffer, size))
+            .map(|count| count as isize)
+            .map_err(|e| CallbackError::io(LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_READ_FAILED, e))
+    })
 }
 
 #[no_mangle]
@@ -76,24 +510,15 @@ pub unsafe extern "C" fn io_handle_write(
     size: usize,
     error: *mut LibbfioErrorRefMut,
 ) -> isize {
-    trace!("io_handle_write");
+    callback("io_handle_write", error, -1, || {
+        let io_handle = io_handle_mut(io_handle)?;
+        check_buffer(buffer, size)?;
 
-    let s = slice::from_raw_parts(buffer, size);
-    match (*io_handle).inner.write(s) {
-        Ok(cnt) => cnt as isize,
-        Err(e) => {
-            libcerror_error_set(
-                error as _,
-                IO_ERR,
-                LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_WRITE_FAILED as i32,
-                CString::new("%s.").unwrap().into_raw(),
-                CString::new(format!("io_handle_write: {:?}", e))
-                    .unwrap()
-                    .into_raw(),
-            );
-            return -1;
-        }
-    }
+        io_handle
+            .write(slice::from_raw_parts(buffer, size))
+            .map(|count| count as isize)
+            .map_err(|e| CallbackError::io(LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_WRITE_FAILED, e))
+    })
 }
 
 #[no_mangle]
@@ -101,53 +526,53 @@ pub unsafe extern "C" fn io_handle_is_open(
     io_handle: *mut IoHandle,
     error: *mut LibbfioErrorRefMut,
 ) -> c_int {
-    trace!("io_handle_is_open");
-    match (*io_handle).is_open {
-        true => 1,
-        false => 0,
-    }
+    callback("io_handle_is_open", error, -1, || {
+        let io_handle = io_handle_mut(io_handle)?;
+
+        Ok((io_handle.is_open && io_handle.inner.is_some()) as c_int)
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn io_handle_seek(
     io_handle: *mut IoHandle,
-    offset: u64,
+    offset: i64,
     whence: c_int,
     error: *mut LibbfioErrorRefMut,
-) -> u64 {
-    trace!("io_handle_seek");
-
-    let seek_from = match whence as u32 {
-        SEEK_SET => SeekFrom::Start(offset),
-        SEEK_END => SeekFrom::End(offset as i64),
-        SEEK_CUR => SeekFrom::Current(offset as i64),
-        _ => {
-            libcerror_error_set(
-                error as _,
-                ARGUMENT_ERR,
-                LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_INVALID_VALUE as i32,
-                CString::new("%s: invalid whence.").unwrap().into_raw(),
-                CString::new("io_handle_seek").unwrap().into_raw(),
-            );
-            return 0;
-        }
-    };
+) -> i64 {
+    callback("io_handle_seek", error, -1, || {
+        let io_handle = io_handle_mut(io_handle)?;
 
-    match (*io_handle).inner.seek(seek_from) {
-        Ok(count) => count,
-        Err(e) => {
-            libcerror_error_set(
-                error as _,
-                IO_ERR,
-                LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_SEEK_FAILED as i32,
-                CString::new("%s.").unwrap().into_raw(),
-                CString::new(format!("io_handle_seek: {:?}", e))
-                    .unwrap()
-                    .into_raw(),
-            );
-            return 0;
-        }
-    }
+        let seek_from = match whence as u32 {
+            SEEK_SET if offset < 0 => {
+                return Err(CallbackError::argument(
+                    LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_VALUE_LESS_THAN_ZERO,
+                    "invalid offset value less than zero",
+                ))
+            }
+            SEEK_SET => SeekFrom::Start(offset as u64),
+            SEEK_END => SeekFrom::End(offset),
+            SEEK_CUR => SeekFrom::Current(offset),
+            _ => {
+                return Err(CallbackError::argument(
+                    LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_UNSUPPORTED_VALUE,
+                    "unsupported whence",
+                ))
+            }
+        };
+
+        let position = io_handle
+            .seek(seek_from)
+            .map_err(|e| CallbackError::io(LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_SEEK_FAILED, e))?;
+
+        i64::try_from(position).map_err(|_| {
+            CallbackError::new(
+                RUNTIME_ERR,
+                LIBCERROR_RUNTIME_ERROR_LIBCERROR_RUNTIME_ERROR_VALUE_EXCEEDS_MAXIMUM,
+                "invalid offset value exceeds maximum",
+            )
+        })
+    })
 }
 
 #[no_mangle]
@@ -156,23 +581,317 @@ pub unsafe extern "C" fn io_handle_get_size(
     size: *mut size64_t,
     error: *mut LibbfioErrorRefMut,
 ) -> c_int {
-    trace!("io_handle_get_size");
-    match (*io_handle).inner.stream_len() {
-        Ok(count) => {
-            *size = count;
-            return 1;
-        }
-        Err(e) => {
-            libcerror_error_set(
-                error as _,
-                IO_ERR,
-                LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_SEEK_FAILED as i32,
-                CString::new("%s.").unwrap().into_raw(),
-                CString::new(format!("io_handle_get_size: {:?}", e))
-                    .unwrap()
-                    .into_raw(),
-            );
-            return 0;
+    callback("io_handle_get_size", error, -1, || {
+        let io_handle = io_handle_mut(io_handle)?;
+
+        if size.is_null() {
+            return Err(CallbackError::argument(
+                LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_INVALID_VALUE,
+                "invalid size",
+            ));
+        }
+
+        *size = io_handle
+            .stream_len()
+            .map_err(|e| CallbackError::io(LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_SEEK_FAILED, e))?;
+
+        Ok(1)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn io_handle_clone(
+    destination_io_handle: *mut *mut IoHandle,
+    source_io_handle: *mut IoHandle,
+    error: *mut LibbfioErrorRefMut,
+) -> c_int {
+    callback("io_handle_clone", error, -1, || {
+        if destination_io_handle.is_null() {
+            return Err(CallbackError::argument(
+                LIBCERROR_ARGUMENT_ERROR_LIBCERROR_ARGUMENT_ERROR_INVALID_VALUE,
+                "invalid destination IO handle",
+            ));
+        }
+
+        if !(*destination_io_handle).is_null() {
+            return Err(CallbackError::new(
+                RUNTIME_ERR,
+                LIBCERROR_RUNTIME_ERROR_LIBCERROR_RUNTIME_ERROR_VALUE_ALREADY_SET,
+                "invalid destination IO handle value already set",
+            ));
+        }
+
+        let io_handle = io_handle_mut(source_io_handle)?.try_clone().map_err(|e| {
+            CallbackError::new(
+                RUNTIME_ERR,
+                LIBCERROR_RUNTIME_ERROR_LIBCERROR_RUNTIME_ERROR_COPY_FAILED,
+                e.to_string(),
+            )
+        })?;
+
+        *destination_io_handle = Box::into_raw(Box::new(io_handle));
+
+        Ok(1)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn io_handle_open(
+    io_handle: *mut IoHandle,
+    access_flags: c_int,
+    error: *mut LibbfioErrorRefMut,
+) -> c_int {
+    callback("io_handle_open", error, -1, || {
+        io_handle_mut(io_handle)?
+            .open(access_flags)
+            .map(|_| 1)
+            .map_err(|e| CallbackError::io(LIBCERROR_IO_ERROR_LIBCERROR_IO_ERROR_OPEN_FAILED, e))
+    })
+}
+
+/// Unlike the other callbacks, this one returns 0 on success.
+#[no_mangle]
+pub unsafe extern "C" fn io_handle_close(
+    io_handle: *mut IoHandle,
+    error: *mut LibbfioErrorRefMut,
+) -> c_int {
+    callback("io_handle_close", error, -1, || {
+        io_handle_mut(io_handle)?.close();
+
+        Ok(0)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn io_handle_exists(
+    io_handle: *mut IoHandle,
+    error: *mut LibbfioErrorRefMut,
+) -> c_int {
+    callback("io_handle_exists", error, -1, || {
+        Ok(io_handle_mut(io_handle)?.exists() as c_int)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::faults::FaultInjector;
+
+    struct PanickingReader;
+
+    impl Read for PanickingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            panic!("a bug in a reader")
         }
     }
+
+    impl Seek for PanickingReader {
+        fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+            panic!("a bug in a reader")
+        }
+    }
+
+    fn raw(io_handle: IoHandle) -> *mut IoHandle {
+        Box::into_raw(Box::new(io_handle))
+    }
+
+    fn cursor() -> *mut IoHandle {
+        raw(IoHandle::new(Cursor::new((0..100).collect::<Vec<u8>>())))
+    }
+
+    /// Checks that a callback set `error`, and frees it.
+    fn assert_error_set(error: &mut LibbfioErrorRefMut) {
+        assert!(!error.is_null());
+
+        unsafe { libcerror_error_free(error as *mut _ as _) };
+        assert!(error.is_null());
+    }
+
+    fn free(mut io_handle: *mut IoHandle) {
+        assert_eq!(unsafe { io_handle_free(&mut io_handle, ptr::null_mut()) }, 1);
+        assert!(io_handle.is_null());
+    }
+
+    #[test]
+    fn test_null_io_handle_fails() {
+        let mut error = ptr::null_mut();
+        let mut buf = [0; 10];
+        let mut size = 0;
+        let null = ptr::null_mut();
+        let read = AccessFlags::read_only().to_int();
+
+        unsafe {
+            assert_eq!(io_handle_read(null, buf.as_mut_ptr(), 10, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_write(null, buf.as_ptr(), 10, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_seek(null, 0, SEEK_SET as c_int, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_get_size(null, &mut size, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_open(null, read, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_close(null, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_is_open(null, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_exists(null, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_free(ptr::null_mut(), &mut error), -1);
+            assert_error_set(&mut error);
+        }
+    }
+
+    #[test]
+    fn test_free_twice_is_a_no_op() {
+        let mut io_handle = cursor();
+
+        unsafe {
+            assert_eq!(io_handle_free(&mut io_handle, ptr::null_mut()), 1);
+            assert_eq!(io_handle_free(&mut io_handle, ptr::null_mut()), 1);
+        }
+    }
+
+    #[test]
+    fn test_invalid_buffers_fail() {
+        let io_handle = cursor();
+        let mut error = ptr::null_mut();
+        let mut buf = [0; 10];
+
+        unsafe {
+            assert_eq!(io_handle_read(io_handle, ptr::null_mut(), 10, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_write(io_handle, ptr::null(), 10, &mut error), -1);
+            assert_error_set(&mut error);
+
+            let size = isize::MAX as usize + 1;
+            assert_eq!(io_handle_read(io_handle, buf.as_mut_ptr(), size, &mut error), -1);
+            assert_error_set(&mut error);
+
+            assert_eq!(io_handle_read(io_handle, buf.as_mut_ptr(), 10, &mut error), 10);
+            assert!(error.is_null());
+        }
+
+        assert_eq!(buf[9], 9);
+        free(io_handle);
+    }
+
+    #[test]
+    fn test_read_and_write_errors_fail() {
+        let source = FaultInjector::new(Cursor::new(vec![0; 100])).fail_reads_after(0);
+        let io_handle = raw(IoHandle::reader(source));
+        let mut error = ptr::null_mut();
+        let mut buf = [0; 10];
+
+        unsafe {
+            assert_eq!(io_handle_read(io_handle, buf.as_mut_ptr(), 10, &mut error), -1);
+            assert_error_set(&mut error);
+            // The source is read-only.
+            assert_eq!(io_handle_write(io_handle, buf.as_ptr(), 10, &mut error), -1);
+            assert_error_set(&mut error);
+        }
+
+        free(io_handle);
+    }
+
+    #[test]
+    fn test_seek_errors_return_minus_one() {
+        let source = FaultInjector::new(Cursor::new(vec![0; 100])).fail_seeks_to(50);
+        let io_handle = raw(IoHandle::reader(source));
+        let mut error = ptr::null_mut();
+
+        unsafe {
+            assert_eq!(io_handle_seek(io_handle, 10, SEEK_SET as c_int, &mut error), 10);
+            assert_eq!(io_handle_seek(io_handle, -5, SEEK_CUR as c_int, &mut error), 5);
+            assert_eq!(io_handle_seek(io_handle, -1, SEEK_END as c_int, &mut error), 99);
+            assert!(error.is_null());
+
+            assert_eq!(io_handle_seek(io_handle, 50, SEEK_SET as c_int, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_seek(io_handle, -1, SEEK_SET as c_int, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_seek(io_handle, 0, 42, &mut error), -1);
+            assert_error_set(&mut error);
+        }
+
+        free(io_handle);
+    }
+
+    #[test]
+    fn test_get_size_checks_its_arguments() {
+        let io_handle = cursor();
+        let mut error = ptr::null_mut();
+        let mut size = 0;
+
+        unsafe {
+            assert_eq!(io_handle_get_size(io_handle, ptr::null_mut(), &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_get_size(io_handle, &mut size, &mut error), 1);
+        }
+
+        assert_eq!(size, 100);
+        free(io_handle);
+    }
+
+    #[test]
+    fn test_clone_errors_fail() {
+        let io_handle = cursor();
+        let bytes = raw(IoHandle::bytes(Arc::from(vec![0; 10])));
+        let mut error = ptr::null_mut();
+        let mut destination = ptr::null_mut();
+
+        unsafe {
+            // Only re-openable sources can be cloned.
+            assert_eq!(io_handle_clone(&mut destination, io_handle, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_clone(ptr::null_mut(), bytes, &mut error), -1);
+            assert_error_set(&mut error);
+
+            assert_eq!(io_handle_clone(&mut destination, bytes, &mut error), 1);
+            assert_eq!(io_handle_clone(&mut destination, bytes, &mut error), -1);
+            assert_error_set(&mut error);
+        }
+
+        free(destination);
+        free(bytes);
+        free(io_handle);
+    }
+
+    #[test]
+    fn test_open_errors_fail() {
+        let io_handle = raw(IoHandle::bytes(Arc::from(vec![0; 10])).read_only());
+        let mut error = ptr::null_mut();
+        let read = AccessFlags::read_only().to_int();
+        let write = AccessFlags::read_write().to_int();
+
+        unsafe {
+            assert_eq!(io_handle_close(io_handle, &mut error), 0);
+            assert_eq!(io_handle_is_open(io_handle, &mut error), 0);
+            assert_eq!(io_handle_open(io_handle, write, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_open(io_handle, read, &mut error), 1);
+            assert_eq!(io_handle_is_open(io_handle, &mut error), 1);
+        }
+
+        free(io_handle);
+    }
+
+    #[test]
+    fn test_panics_do_not_unwind_into_c() {
+        let io_handle = raw(IoHandle::reader(PanickingReader));
+        let mut error = ptr::null_mut();
+        let mut buf = [0; 10];
+        let mut size = 0;
+
+        unsafe {
+            assert_eq!(io_handle_read(io_handle, buf.as_mut_ptr(), 10, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_seek(io_handle, 0, SEEK_SET as c_int, &mut error), -1);
+            assert_error_set(&mut error);
+            assert_eq!(io_handle_get_size(io_handle, &mut size, &mut error), -1);
+            assert_error_set(&mut error);
+        }
+
+        free(io_handle);
+    }
 }
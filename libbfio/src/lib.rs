@@ -4,8 +4,19 @@
 #[macro_use]
 extern crate libyal_rs_common;
 
+pub mod access;
+pub mod cache;
+pub mod faults;
 pub mod handle;
+#[cfg(feature = "http")]
+pub mod http;
 mod io_handle;
+pub mod mmap;
+pub mod overlay;
+pub mod segments;
+mod seek;
+pub mod tolerant;
 pub mod ffi_error;
 pub mod error;
+pub mod window;
 //mod libbfio;
\ No newline at end of file
@@ -0,0 +1,184 @@
+//! A read-only memory map of a file, exposed as an IO source.
+//!
+//! Reads are copied straight out of the map, without a syscall per read.
+//! Files which can't be mapped at once (like large images on 32-bit systems) are mapped
+//! a window at a time, moving the window as the position moves.
+//!
+//! The file must not be truncated while it is mapped, since reading unbacked pages of a map
+//! crashes the process.
+use crate::seek::seek_position;
+use memmap2::{Mmap, MmapOptions};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The size of the windows of files which can't be mapped at once.
+pub const DEFAULT_WINDOW_SIZE: u64 = 256 * 1024 * 1024;
+
+pub struct MmapReader {
+    file: File,
+    len: u64,
+    window_size: u64,
+    /// The offset of the mapped window in the file, and the map.
+    window: Option<(u64, Mmap)>,
+    position: u64,
+}
+
+impl MmapReader {
+    /// Maps the whole file, or falls back to windows of `DEFAULT_WINDOW_SIZE`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = MmapReader::with_window_size(path, DEFAULT_WINDOW_SIZE)?;
+
+        if reader.len > DEFAULT_WINDOW_SIZE && reader.len <= usize::MAX as u64 {
+            if let Ok(map) = reader.map(0, reader.len) {
+                reader.window_size = reader.len;
+                reader.window = Some((0, map));
+            }
+        }
+
+        Ok(reader)
+    }
+
+    /// Maps the file a window of `window_size` bytes at a time.
+    pub fn with_window_size(path: impl AsRef<Path>, window_size: u64) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        Ok(MmapReader {
+            file,
+            len,
+            window_size: window_size.max(1),
+            window: None,
+            position: 0,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn map(&self, offset: u64, len: u64) -> io::Result<Mmap> {
+        unsafe {
+            MmapOptions::new()
+                .offset(offset)
+                .len(len as usize)
+                .map(&self.file)
+        }
+    }
+
+    /// The mapped bytes from the current position to the end of its window.
+    fn mapped(&mut self) -> io::Result<&[u8]> {
+        let is_mapped = match &self.window {
+            Some((offset, map)) => {
+                *offset <= self.position && self.position < *offset + map.len() as u64
+            }
+            None => false,
+        };
+
+        if !is_mapped {
+            let offset = self.position - self.position % self.window_size;
+            let len = self.window_size.min(self.len - offset);
+
+            // Unmap the previous window first, so the two never take address space together.
+            self.window = None;
+            self.window = Some((offset, self.map(offset, len)?));
+        }
+
+        match &self.window {
+            Some((offset, map)) => Ok(&map[(self.position - offset) as usize..]),
+            None => unreachable!("the window was just mapped"),
+        }
+    }
+}
+
+impl Read for MmapReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut total = 0;
+
+        // Fill the whole buffer even across windows, since libbfio treats short reads as errors.
+        while total < buf.len() && self.position < self.len {
+            let mapped = self.mapped()?;
+            let count = mapped.len().min(buf.len() - total);
+
+            buf[total..total + count].copy_from_slice(&mapped[..count]);
+
+            total += count;
+            self.position += count as u64;
+        }
+
+        Ok(total)
+    }
+}
+
+impl Seek for MmapReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.len, pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn data() -> Vec<u8> {
+        (0..=255).collect()
+    }
+
+    fn image(dir: &TempDir) -> std::path::PathBuf {
+        let path = dir.path().join("image.dd");
+        fs::write(&path, data()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = TempDir::new("mmap").unwrap();
+        let mut reader = MmapReader::open(image(&dir)).unwrap();
+        let mut buf = vec![];
+
+        reader.seek(SeekFrom::Start(16)).unwrap();
+        reader.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, &data()[16..]);
+        assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), 256);
+    }
+
+    #[test]
+    fn test_read_across_windows() {
+        let dir = TempDir::new("mmap").unwrap();
+        let mut reader = MmapReader::with_window_size(image(&dir), 10).unwrap();
+        let mut buf = [0; 25];
+
+        reader.seek(SeekFrom::Start(95)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data()[95..120]);
+
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data()[3..28]);
+
+        reader.seek(SeekFrom::End(-5)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 5);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let dir = TempDir::new("mmap").unwrap();
+        let path = dir.path().join("empty.dd");
+        fs::write(&path, b"").unwrap();
+
+        let mut reader = MmapReader::open(path).unwrap();
+        let mut buf = vec![];
+
+        assert!(reader.is_empty());
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 0);
+    }
+}
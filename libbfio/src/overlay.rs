@@ -0,0 +1,441 @@
+//! A copy-on-write overlay, which keeps all the writes to a source in a separate delta.
+//!
+//! Reads merge the delta over the source, which is never written to, unless the delta is
+//! explicitly committed. The delta is made of whole blocks, and lives in memory or in a file.
+//!
+//! Clones of an `Overlay` share the source and the delta, but each has a position of its own,
+//! so one clone can be handed to a `Handle` while another commits, discards or exports the delta.
+use crate::seek::seek_position;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The size of the blocks of the delta.
+pub const BLOCK_SIZE: u64 = 4096;
+
+enum Delta {
+    Memory(HashMap<u64, Vec<u8>>),
+    /// Blocks are appended to the file, `slots` maps block indices to their slot in it.
+    File {
+        file: File,
+        slots: HashMap<u64, u64>,
+    },
+}
+
+impl Delta {
+    fn get(&mut self, index: u64) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            Delta::Memory(blocks) => Ok(blocks.get(&index).cloned()),
+            Delta::File { file, slots } => match slots.get(&index) {
+                Some(slot) => {
+                    let mut block = vec![0; BLOCK_SIZE as usize];
+                    file.seek(SeekFrom::Start(slot * BLOCK_SIZE))?;
+                    file.read_exact(&mut block)?;
+                    Ok(Some(block))
+                }
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn put(&mut self, index: u64, block: Vec<u8>) -> io::Result<()> {
+        match self {
+            Delta::Memory(blocks) => {
+                blocks.insert(index, block);
+            }
+            Delta::File { file, slots } => {
+                let next_slot = slots.len() as u64;
+                let slot = *slots.entry(index).or_insert(next_slot);
+
+                file.seek(SeekFrom::Start(slot * BLOCK_SIZE))?;
+                file.write_all(&block)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The indices of the blocks in the delta, in order.
+    fn indices(&self) -> Vec<u64> {
+        let mut indices: Vec<u64> = match self {
+            Delta::Memory(blocks) => blocks.keys().cloned().collect(),
+            Delta::File { slots, .. } => slots.keys().cloned().collect(),
+        };
+        indices.sort();
+        indices
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        match self {
+            Delta::Memory(blocks) => blocks.clear(),
+            Delta::File { file, slots } => {
+                slots.clear();
+                file.set_len(0)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct State<T> {
+    inner: T,
+    delta: Delta,
+    /// The size of the source, which the delta may extend.
+    source_len: u64,
+    len: u64,
+}
+
+impl<T: Read + Seek> State<T> {
+    /// Copies the bytes at `offset` into `buf`, which doesn't cross a block boundary.
+    fn read_block_part(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let index = offset / BLOCK_SIZE;
+        let offset_in_block = (offset % BLOCK_SIZE) as usize;
+
+        if let Some(block) = self.delta.get(index)? {
+            buf.copy_from_slice(&block[offset_in_block..offset_in_block + buf.len()]);
+            return Ok(());
+        }
+
+        let from_source = self.source_len.saturating_sub(offset).min(buf.len() as u64) as usize;
+
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(&mut buf[..from_source])?;
+        buf[from_source..].iter_mut().for_each(|b| *b = 0);
+
+        Ok(())
+    }
+
+    /// The current content of the block at `index`, zero-padded to the block size.
+    fn block(&mut self, index: u64) -> io::Result<Vec<u8>> {
+        if let Some(block) = self.delta.get(index)? {
+            return Ok(block);
+        }
+
+        let mut block = vec![0; BLOCK_SIZE as usize];
+        self.read_block_part(index * BLOCK_SIZE, &mut block)?;
+
+        Ok(block)
+    }
+}
+
+pub struct Overlay<T> {
+    state: Arc<Mutex<State<T>>>,
+    position: u64,
+}
+
+impl<T> Clone for Overlay<T> {
+    fn clone(&self) -> Self {
+        Overlay {
+            state: self.state.clone(),
+            position: self.position,
+        }
+    }
+}
+
+impl<T: Read + Seek> Overlay<T> {
+    /// An overlay with an in-memory delta.
+    pub fn new(inner: T) -> io::Result<Self> {
+        Overlay::with_delta(inner, Delta::Memory(HashMap::new()))
+    }
+
+    /// An overlay with its delta in the file at `path`, which is created (or truncated).
+    pub fn with_delta_file(inner: T, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        Overlay::with_delta(
+            inner,
+            Delta::File {
+                file,
+                slots: HashMap::new(),
+            },
+        )
+    }
+
+    fn with_delta(mut inner: T, delta: Delta) -> io::Result<Self> {
+        let source_len = inner.seek(SeekFrom::End(0))?;
+
+        Ok(Overlay {
+            state: Arc::new(Mutex::new(State {
+                inner,
+                delta,
+                source_len,
+                len: source_len,
+            })),
+            position: 0,
+        })
+    }
+
+    /// The size of the source with the delta applied.
+    pub fn len(&self) -> u64 {
+        self.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_modified(&self) -> bool {
+        let state = self.lock();
+        !state.delta.indices().is_empty() || state.len != state.source_len
+    }
+
+    /// The byte ranges of the blocks in the delta, with adjacent ranges merged.
+    pub fn modified_ranges(&self) -> Vec<Range<u64>> {
+        let state = self.lock();
+        let mut ranges: Vec<Range<u64>> = vec![];
+
+        for index in state.delta.indices() {
+            let start = index * BLOCK_SIZE;
+            let end = (start + BLOCK_SIZE).min(state.len);
+
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+
+        ranges
+    }
+
+    /// Drops all the writes, so reads see the source again.
+    pub fn discard(&self) -> io::Result<()> {
+        let mut state = self.lock();
+
+        state.delta.clear()?;
+        state.len = state.source_len;
+
+        Ok(())
+    }
+
+    /// Writes the delta to `out`, as a sequence of blocks, in order.
+    /// Each block is its offset (a little-endian u64), its length (a little-endian u32)
+    /// and its bytes. The last block is cut at the end of the data.
+    pub fn export_delta(&self, mut out: impl Write) -> io::Result<()> {
+        let mut state = self.lock();
+
+        for index in state.delta.indices() {
+            let offset = index * BLOCK_SIZE;
+            let block = state.block(index)?;
+            let len = (state.len - offset).min(BLOCK_SIZE) as usize;
+
+            out.write_all(&offset.to_le_bytes())?;
+            out.write_all(&(len as u32).to_le_bytes())?;
+            out.write_all(&block[..len])?;
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: Read + Write + Seek> Overlay<T> {
+    /// Writes the delta to the source, and clears it.
+    pub fn commit(&self) -> io::Result<()> {
+        let mut state = self.lock();
+
+        for index in state.delta.indices() {
+            let offset = index * BLOCK_SIZE;
+            let block = state.block(index)?;
+            let len = (state.len - offset).min(BLOCK_SIZE) as usize;
+
+            state.inner.seek(SeekFrom::Start(offset))?;
+            state.inner.write_all(&block[..len])?;
+        }
+
+        state.inner.flush()?;
+        state.delta.clear()?;
+        state.source_len = state.len;
+
+        Ok(())
+    }
+}
+
+impl<T: Read + Seek> Read for Overlay<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.lock();
+
+        let start = self.position;
+        let end = start
+            .saturating_add(buf.len() as u64)
+            .min(state.len.max(start));
+        let mut offset = start;
+
+        while offset < end {
+            let part_end = ((offset / BLOCK_SIZE + 1) * BLOCK_SIZE).min(end);
+            let part = &mut buf[(offset - start) as usize..(part_end - start) as usize];
+
+            state.read_block_part(offset, part)?;
+
+            offset = part_end;
+        }
+
+        drop(state);
+        self.position = end;
+
+        Ok((end - start) as usize)
+    }
+}
+
+impl<T: Read + Seek> Write for Overlay<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.lock();
+
+        let start = self.position;
+        let end = start.checked_add(buf.len() as u64).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "write past the maximal offset")
+        })?;
+        let mut offset = start;
+
+        while offset < end {
+            let index = offset / BLOCK_SIZE;
+            let offset_in_block = (offset % BLOCK_SIZE) as usize;
+            let part_end = ((index + 1) * BLOCK_SIZE).min(end);
+            let part = &buf[(offset - start) as usize..(part_end - start) as usize];
+
+            let mut block = state.block(index)?;
+            block[offset_in_block..offset_in_block + part.len()].copy_from_slice(part);
+            state.delta.put(index, block)?;
+
+            offset = part_end;
+        }
+
+        state.len = state.len.max(end);
+        drop(state);
+        self.position = end;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.lock().delta {
+            Delta::File { file, .. } => file.flush(),
+            Delta::Memory(_) => Ok(()),
+        }
+    }
+}
+
+impl<T: Read + Seek> Seek for Overlay<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.len(), pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempdir::TempDir;
+
+    fn source() -> Cursor<Vec<u8>> {
+        Cursor::new(vec![1; 10000])
+    }
+
+    fn read_all(overlay: &Overlay<Cursor<Vec<u8>>>) -> Vec<u8> {
+        let mut reader = overlay.clone();
+        let mut buf = vec![];
+
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    fn write_at(overlay: &Overlay<Cursor<Vec<u8>>>, offset: u64, data: &[u8]) {
+        let mut writer = overlay.clone();
+
+        writer.seek(SeekFrom::Start(offset)).unwrap();
+        writer.write_all(data).unwrap();
+    }
+
+    #[test]
+    fn test_writes_go_to_the_delta() {
+        let overlay = Overlay::new(source()).unwrap();
+        write_at(&overlay, 4090, &[2; 10]);
+
+        let data = read_all(&overlay);
+        assert_eq!(&data[4085..4090], &[1; 5]);
+        assert_eq!(&data[4090..4100], &[2; 10]);
+        assert_eq!(&data[4100..4105], &[1; 5]);
+
+        assert_eq!(overlay.modified_ranges(), vec![0..8192]);
+        assert!(overlay.lock().inner.get_ref().iter().all(|b| *b == 1));
+    }
+
+    #[test]
+    fn test_writes_past_the_end_extend_the_source() {
+        let overlay = Overlay::new(source()).unwrap();
+        write_at(&overlay, 10005, b"end");
+
+        let data = read_all(&overlay);
+        assert_eq!(data.len(), 10008);
+        assert_eq!(&data[10000..], b"\0\0\0\0\0end");
+    }
+
+    #[test]
+    fn test_discard() {
+        let overlay = Overlay::new(source()).unwrap();
+        write_at(&overlay, 10, &[2; 10000]);
+
+        overlay.discard().unwrap();
+
+        assert!(!overlay.is_modified());
+        assert_eq!(read_all(&overlay), vec![1; 10000]);
+    }
+
+    #[test]
+    fn test_commit() {
+        let overlay = Overlay::new(source()).unwrap();
+        write_at(&overlay, 9998, &[2; 4]);
+
+        overlay.commit().unwrap();
+
+        assert!(!overlay.is_modified());
+        let source = overlay.lock().inner.get_ref().clone();
+        assert_eq!(source.len(), 10002);
+        assert_eq!(&source[9996..], &[1, 1, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_export_delta() {
+        let overlay = Overlay::new(source()).unwrap();
+        write_at(&overlay, 8192, &[2; 2]);
+
+        let mut delta = vec![];
+        overlay.export_delta(&mut delta).unwrap();
+
+        assert_eq!(&delta[..8], &8192_u64.to_le_bytes());
+        assert_eq!(&delta[8..12], &(10000_u32 - 8192).to_le_bytes());
+        assert_eq!(&delta[12..14], &[2, 2]);
+        assert_eq!(delta.len(), 12 + 10000 - 8192);
+    }
+
+    #[test]
+    fn test_delta_file() {
+        let dir = TempDir::new("overlay").unwrap();
+        let overlay = Overlay::with_delta_file(source(), dir.path().join("delta")).unwrap();
+
+        write_at(&overlay, 5000, &[3; 3]);
+        write_at(&overlay, 100, &[2; 3]);
+        write_at(&overlay, 5003, &[4; 1]);
+
+        let data = read_all(&overlay);
+        assert_eq!(&data[99..104], &[1, 2, 2, 2, 1]);
+        assert_eq!(&data[4999..5005], &[1, 3, 3, 3, 4, 1]);
+        assert_eq!(
+            std::fs::metadata(dir.path().join("delta")).unwrap().len(),
+            2 * BLOCK_SIZE
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! The seeking shared by the in-process sources, which only keep a position.
+use std::io::{self, SeekFrom};
+
+/// The position after seeking to `pos`, from `position` in a source of `len` bytes.
+/// Seeking past the end is allowed, like it is for files.
+pub(crate) fn seek_position(position: u64, len: u64, pos: SeekFrom) -> io::Result<u64> {
+    let (base, delta) = match pos {
+        SeekFrom::Start(p) => return Ok(p),
+        SeekFrom::Current(p) => (position, p),
+        SeekFrom::End(p) => (len, p),
+    };
+
+    let new_position = if delta >= 0 {
+        base.checked_add(delta as u64)
+    } else {
+        base.checked_sub(delta.wrapping_neg() as u64)
+    };
+
+    new_position.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seek_position() {
+        assert_eq!(seek_position(5, 10, SeekFrom::Start(20)).unwrap(), 20);
+        assert_eq!(seek_position(5, 10, SeekFrom::Current(-5)).unwrap(), 0);
+        assert_eq!(seek_position(5, 10, SeekFrom::Current(3)).unwrap(), 8);
+        assert_eq!(seek_position(5, 10, SeekFrom::End(-10)).unwrap(), 0);
+        assert!(seek_position(5, 10, SeekFrom::End(i64::MIN)).is_err());
+        assert!(seek_position(5, 10, SeekFrom::Current(-6)).is_err());
+        assert!(seek_position(u64::MAX, 10, SeekFrom::Current(1)).is_err());
+        assert_eq!(
+            seek_position(5, 10, SeekFrom::End(-11)).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
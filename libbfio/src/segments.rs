@@ -0,0 +1,297 @@
+//! Split raw images (like `image.001`, `image.002`, ...), exposed as a single IO source.
+//!
+//! All the segments but the last must have the same size, and the last one can't be larger.
+use crate::seek::seek_position;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+struct Segment {
+    file: File,
+    /// The offset of the segment in the image.
+    offset: u64,
+    size: u64,
+}
+
+pub struct SegmentedReader {
+    segments: Vec<Segment>,
+    paths: Vec<PathBuf>,
+    len: u64,
+    position: u64,
+}
+
+impl SegmentedReader {
+    /// Opens the image which starts with `first_segment`, discovering the rest of the segments.
+    pub fn open(first_segment: impl AsRef<Path>) -> io::Result<Self> {
+        SegmentedReader::from_paths(discover_segments(first_segment)?)
+    }
+
+    /// Opens the image made of `paths`, in order.
+    pub fn from_paths(paths: Vec<PathBuf>) -> io::Result<Self> {
+        let mut segments = Vec::with_capacity(paths.len());
+        let mut offset = 0;
+
+        for path in paths.iter() {
+            let file = File::open(path)?;
+            let size = file.metadata()?.len();
+
+            segments.push(Segment { file, offset, size });
+            offset += size;
+        }
+
+        validate_sizes(&segments, &paths)?;
+
+        Ok(SegmentedReader {
+            segments,
+            paths,
+            len: offset,
+            position: 0,
+        })
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The index of the segment which contains `offset`, which must be before the end.
+    fn segment_index(&self, offset: u64) -> usize {
+        match self
+            .segments
+            .binary_search_by(|segment| segment.offset.cmp(&offset))
+        {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn validate_sizes(segments: &[Segment], paths: &[PathBuf]) -> io::Result<()> {
+    let segment_size = match segments.first() {
+        Some(first) => first.size,
+        None => {
+            return Err(invalid_data(
+                "an image needs at least one segment".to_owned(),
+            ))
+        }
+    };
+
+    for (i, segment) in segments.iter().enumerate() {
+        let is_last = i == segments.len() - 1;
+
+        if segment.size == 0 && segments.len() > 1 {
+            return Err(invalid_data(format!(
+                "segment {} is empty",
+                paths[i].display()
+            )));
+        }
+
+        if (!is_last && segment.size != segment_size) || segment.size > segment_size {
+            return Err(invalid_data(format!(
+                "segment {} is {} bytes, while the first segment is {} bytes",
+                paths[i].display(),
+                segment.size,
+                segment_size
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The paths of all the segments of the image which starts with `first_segment`.
+///
+/// Segments are named with a numeric extension (`.000`, `.001`, ...) counting up from the
+/// first segment, or with an alphabetic one (`.aa`, `.ab`, ...) starting at `aa`.
+pub fn discover_segments(first_segment: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let first_segment = first_segment.as_ref();
+
+    // Fails if the first segment is missing.
+    first_segment.metadata()?;
+
+    let extension = first_segment
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+
+    let is_numeric = !extension.is_empty() && extension.bytes().all(|b| b.is_ascii_digit());
+    let is_alphabetic = extension.len() >= 2 && extension.bytes().all(|b| b == b'a');
+
+    if !is_numeric && !is_alphabetic {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is not named like the first segment of a split image",
+                first_segment.display()
+            ),
+        ));
+    }
+
+    let mut paths = vec![first_segment.to_path_buf()];
+    let mut extension = extension.to_owned();
+
+    loop {
+        extension = match next_extension(&extension, is_numeric) {
+            Some(next) => next,
+            None => break,
+        };
+
+        let path = first_segment.with_extension(&extension);
+        if !path.is_file() {
+            break;
+        }
+
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// The extension of the segment which follows the one with `extension`, if there can be one.
+fn next_extension(extension: &str, is_numeric: bool) -> Option<String> {
+    if is_numeric {
+        let number: u64 = extension.parse().ok()?;
+
+        return Some(format!(
+            "{:0width$}",
+            number.checked_add(1)?,
+            width = extension.len()
+        ));
+    }
+
+    let mut letters = extension.as_bytes().to_vec();
+
+    for letter in letters.iter_mut().rev() {
+        if *letter < b'z' {
+            *letter += 1;
+            return String::from_utf8(letters).ok();
+        }
+
+        *letter = b'a';
+    }
+
+    None
+}
+
+impl Read for SegmentedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut total = 0;
+
+        // Fill the whole buffer even across segments, since libbfio treats short reads as errors.
+        while total < buf.len() && self.position < self.len {
+            let index = self.segment_index(self.position);
+            let segment = &mut self.segments[index];
+
+            let offset_in_segment = self.position - segment.offset;
+            let count = ((segment.size - offset_in_segment) as usize).min(buf.len() - total);
+
+            segment.file.seek(SeekFrom::Start(offset_in_segment))?;
+            let read_count = segment.file.read(&mut buf[total..total + count])?;
+
+            if read_count == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "a segment was truncated while the image was open",
+                ));
+            }
+
+            total += read_count;
+            self.position += read_count as u64;
+        }
+
+        Ok(total)
+    }
+}
+
+impl Seek for SegmentedReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.len, pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn data() -> Vec<u8> {
+        (0..100).collect()
+    }
+
+    /// Splits `data()` into segments of `segment_size` bytes, named `image.<extension>`.
+    fn split(dir: &TempDir, segment_size: usize, extensions: &[&str]) -> PathBuf {
+        for (chunk, extension) in data().chunks(segment_size).zip(extensions) {
+            fs::write(dir.path().join(format!("image.{}", extension)), chunk).unwrap();
+        }
+
+        dir.path().join(format!("image.{}", extensions[0]))
+    }
+
+    #[test]
+    fn test_read_across_segments() {
+        let dir = TempDir::new("segments").unwrap();
+        let first = split(&dir, 40, &["001", "002", "003"]);
+
+        let mut reader = SegmentedReader::open(first).unwrap();
+        let mut buf = vec![];
+
+        assert_eq!(reader.paths().len(), 3);
+        assert_eq!(reader.len(), 100);
+
+        reader.seek(SeekFrom::Start(30)).unwrap();
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, &data()[30..]);
+    }
+
+    #[test]
+    fn test_discover_alphabetic_segments() {
+        let dir = TempDir::new("segments").unwrap();
+        let first = split(&dir, 30, &["aa", "ab", "ac", "ad"]);
+
+        let paths = discover_segments(first).unwrap();
+
+        assert_eq!(paths.len(), 4);
+        assert!(paths[3].ends_with("image.ad"));
+    }
+
+    #[test]
+    fn test_next_extension() {
+        assert_eq!(next_extension("009", true).unwrap(), "010");
+        assert_eq!(next_extension("999", true).unwrap(), "1000");
+        assert_eq!(next_extension("az", false).unwrap(), "ba");
+        assert!(next_extension("zz", false).is_none());
+    }
+
+    #[test]
+    fn test_segment_sizes_are_validated() {
+        let dir = TempDir::new("segments").unwrap();
+        let first = split(&dir, 40, &["001", "002", "003"]);
+        fs::write(dir.path().join("image.002"), &data()[..10]).unwrap();
+
+        let err = SegmentedReader::open(first).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_first_segment_must_be_named_like_one() {
+        let dir = TempDir::new("segments").unwrap();
+        let path = dir.path().join("image.dd");
+        fs::write(&path, data()).unwrap();
+
+        let err = discover_segments(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,277 @@
+//! An IO source for damaged media, which reads unreadable blocks as zeros.
+//!
+//! When a read fails, the requested bytes are read again a block at a time, and the blocks
+//! which still fail are zero-filled and recorded as bad ranges.
+//! Ranges which are known to be bad (like the ones a ddrescue mapfile marks as not rescued)
+//! are zero-filled without reading them at all, so a failing drive isn't hammered again.
+use crate::seek::seek_position;
+use log::warn;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The default size of the blocks which are zero-filled on errors (a sector).
+pub const DEFAULT_BLOCK_SIZE: u64 = 512;
+
+/// The bad ranges of a `TolerantReader`, which stay available after it was moved into a handle.
+#[derive(Debug, Clone, Default)]
+pub struct BadRanges(Arc<Mutex<Vec<Range<u64>>>>);
+
+impl BadRanges {
+    pub fn new() -> Self {
+        BadRanges::default()
+    }
+
+    /// The bad ranges, sorted and with adjacent ranges merged.
+    pub fn to_vec(&self) -> Vec<Range<u64>> {
+        self.lock().clone()
+    }
+
+    /// Marks `range` as bad.
+    pub fn insert(&self, range: Range<u64>) {
+        if range.start >= range.end {
+            return;
+        }
+
+        let mut ranges = self.lock();
+        ranges.push(range);
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+        for range in ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        *ranges = merged;
+    }
+
+    pub fn overlaps(&self, range: &Range<u64>) -> bool {
+        self.lock()
+            .iter()
+            .any(|bad| bad.start < range.end && range.start < bad.end)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Range<u64>>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub struct TolerantReader<T> {
+    inner: T,
+    block_size: u64,
+    bad_ranges: BadRanges,
+    len: u64,
+    position: u64,
+}
+
+impl<T: Read + Seek> TolerantReader<T> {
+    pub fn new(mut inner: T) -> io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+
+        Ok(TolerantReader {
+            inner,
+            block_size: DEFAULT_BLOCK_SIZE,
+            bad_ranges: BadRanges::new(),
+            len,
+            position: 0,
+        })
+    }
+
+    /// Pre-marks the ranges which the ddrescue mapfile at `path` does not mark as rescued.
+    pub fn with_mapfile(inner: T, path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = TolerantReader::new(inner)?;
+
+        for range in parse_ddrescue_mapfile(BufReader::new(File::open(path)?))? {
+            reader.bad_ranges.insert(range);
+        }
+
+        Ok(reader)
+    }
+
+    /// The size of the blocks which are retried and zero-filled on errors.
+    pub fn block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// The ranges which were zero-filled, or will be.
+    pub fn bad_ranges(&self) -> BadRanges {
+        self.bad_ranges.clone()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(buf)
+    }
+}
+
+impl<T: Read + Seek> Read for TolerantReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self.position;
+        let count = (buf.len() as u64).min(self.len.saturating_sub(start)) as usize;
+        let end = start + count as u64;
+
+        if count == 0 {
+            return Ok(0);
+        }
+
+        if !self.bad_ranges.overlaps(&(start..end))
+            && self.read_exact_at(&mut buf[..count], start).is_ok()
+        {
+            self.position = end;
+            return Ok(count);
+        }
+
+        let mut offset = start;
+
+        while offset < end {
+            let block_end = ((offset / self.block_size + 1) * self.block_size).min(end);
+            let block = &mut buf[(offset - start) as usize..(block_end - start) as usize];
+
+            if self.bad_ranges.overlaps(&(offset..block_end)) {
+                block.iter_mut().for_each(|b| *b = 0);
+            } else if let Err(e) = self.read_exact_at(block, offset) {
+                warn!(
+                    "Zero-filling unreadable bytes {}..{}: {}",
+                    offset, block_end, e
+                );
+
+                block.iter_mut().for_each(|b| *b = 0);
+                self.bad_ranges.insert(offset..block_end);
+            }
+
+            offset = block_end;
+        }
+
+        self.position = end;
+
+        Ok(count)
+    }
+}
+
+impl<T> Seek for TolerantReader<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.len, pos)?;
+        Ok(self.position)
+    }
+}
+
+/// The ranges of a ddrescue mapfile which were not rescued (any status but `+`).
+pub fn parse_ddrescue_mapfile(mapfile: impl BufRead) -> io::Result<Vec<Range<u64>>> {
+    let invalid = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid ddrescue mapfile line: {}", line),
+        )
+    };
+
+    let mut ranges = vec![];
+
+    for line in mapfile.lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if fields.is_empty() || fields[0].starts_with('#') {
+            continue;
+        }
+
+        // The status line (`current_pos current_status [current_pass]`) has no size.
+        if fields.len() < 3 || !fields[1].starts_with("0x") {
+            continue;
+        }
+
+        let position = parse_number(fields[0]).ok_or_else(|| invalid(&line))?;
+        let size = parse_number(fields[1]).ok_or_else(|| invalid(&line))?;
+
+        if fields[2] != "+" && size > 0 {
+            let end = position.checked_add(size).ok_or_else(|| invalid(&line))?;
+            ranges.push(position..end);
+        }
+    }
+
+    Ok(ranges)
+}
+
+fn parse_number(field: &str) -> Option<u64> {
+    if field.starts_with("0x") || field.starts_with("0X") {
+        u64::from_str_radix(&field[2..], 16).ok()
+    } else {
+        field.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::faults::FaultInjector;
+    use std::io::Cursor;
+
+    fn data() -> Vec<u8> {
+        (1..=100).collect()
+    }
+
+    const MAPFILE: &str = "# Mapfile. Created by GNU ddrescue version 1.23
+# current_pos  current_status  current_pass
+0x00000028     +               1
+#      pos        size  status
+0x00000000  0x00000014  +
+0x00000014  0x0000000A  -
+0x0000001E  0x00000046  +
+";
+
+    #[test]
+    fn test_unreadable_blocks_are_zero_filled() {
+        let source = FaultInjector::new(Cursor::new(data())).fail_reads_in(25..27);
+        let mut reader = TolerantReader::new(source).unwrap().block_size(10);
+        let mut buf = vec![];
+
+        reader.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(&buf[..20], &data()[..20]);
+        assert_eq!(&buf[20..30], &[0; 10]);
+        assert_eq!(&buf[30..], &data()[30..]);
+        assert_eq!(reader.bad_ranges().to_vec(), vec![20..30]);
+    }
+
+    #[test]
+    fn test_parse_ddrescue_mapfile() {
+        let ranges = parse_ddrescue_mapfile(Cursor::new(MAPFILE)).unwrap();
+
+        assert_eq!(ranges, vec![20..30]);
+        assert!(parse_ddrescue_mapfile(Cursor::new("0x0 0xZZ -")).is_err());
+        assert!(parse_ddrescue_mapfile(Cursor::new("0xFFFFFFFFFFFFFFFF 0x10 -")).is_err());
+    }
+
+    #[test]
+    fn test_pre_marked_ranges_are_not_read() {
+        let source = FaultInjector::new(Cursor::new(data())).fail_reads_after(0);
+        let mut reader = TolerantReader::new(source).unwrap().block_size(10);
+        let mut buf = [1; 15];
+
+        reader.bad_ranges().insert(0..100);
+        reader.read_exact(&mut buf).unwrap();
+
+        assert_eq!(buf, [0; 15]);
+        assert_eq!(reader.into_inner().reads(), 0);
+    }
+
+    #[test]
+    fn test_bad_ranges_are_merged() {
+        let ranges = BadRanges::new();
+        ranges.insert(10..20);
+        ranges.insert(30..40);
+        ranges.insert(20..30);
+        ranges.insert(50..50);
+
+        assert_eq!(ranges.to_vec(), vec![10..40]);
+    }
+}
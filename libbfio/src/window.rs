@@ -0,0 +1,174 @@
+//! A byte range of an IO source, exposed as an IO source of its own.
+//!
+//! Offsets are relative to the start of the window, and reads and writes never go past its end.
+use crate::seek::seek_position;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub struct Window<T> {
+    inner: T,
+    offset: u64,
+    size: u64,
+    position: u64,
+}
+
+impl<T> Window<T> {
+    pub fn new(inner: T, offset: u64, size: u64) -> Self {
+        Window {
+            inner,
+            offset,
+            size,
+            position: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// The number of bytes from the current position to the end of the window.
+    fn remaining(&self) -> u64 {
+        self.size.saturating_sub(self.position)
+    }
+}
+
+impl<T: Seek> Window<T> {
+    fn seek_inner(&mut self) -> io::Result<()> {
+        let inner_position = self.offset.checked_add(self.position).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "window offset overflows")
+        })?;
+
+        self.inner.seek(SeekFrom::Start(inner_position))?;
+
+        Ok(())
+    }
+}
+
+impl<T: Read + Seek> Read for Window<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (buf.len() as u64).min(self.remaining()) as usize;
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        self.seek_inner()?;
+
+        // Fill the buffer even if the source reads less at once,
+        // since libbfio treats short reads as errors.
+        let mut total = 0;
+
+        while total < len {
+            match self.inner.read(&mut buf[total..len]) {
+                Ok(0) => break,
+                Ok(count) => total += count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.position += total as u64;
+
+        Ok(total)
+    }
+}
+
+impl<T: Write + Seek> Write for Window<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = (buf.len() as u64).min(self.remaining()) as usize;
+
+        if len == 0 && !buf.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "cannot write past the end of the window",
+            ));
+        }
+
+        self.seek_inner()?;
+        let count = self.inner.write(&buf[..len])?;
+        self.position += count as u64;
+
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Seek> Seek for Window<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn window() -> Window<Cursor<Vec<u8>>> {
+        Window::new(Cursor::new(b"0123456789".to_vec()), 2, 5)
+    }
+
+    #[test]
+    fn test_read_is_bounded() {
+        let mut buf = vec![];
+        window().read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf, b"23456");
+    }
+
+    /// A source which reads a single byte at a time.
+    struct ByteReader(Cursor<Vec<u8>>);
+
+    impl Read for ByteReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl Seek for ByteReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_short_reads_of_the_source_are_filled() {
+        let mut window = Window::new(ByteReader(Cursor::new(b"0123456789".to_vec())), 2, 5);
+        let mut buf = [0; 8];
+
+        assert_eq!(window.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"23456");
+        assert_eq!(window.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_seek_is_relative() {
+        let mut window = window();
+        let mut buf = [0; 2];
+
+        assert_eq!(window.seek(SeekFrom::End(-2)).unwrap(), 3);
+        window.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"56");
+
+        assert_eq!(window.seek(SeekFrom::Current(-4)).unwrap(), 1);
+        window.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"34");
+
+        assert!(window.seek(SeekFrom::Current(-10)).is_err());
+    }
+
+    #[test]
+    fn test_write_is_bounded() {
+        let mut window = window();
+
+        window.seek(SeekFrom::Start(3)).unwrap();
+        assert_eq!(window.write(b"abcd").unwrap(), 2);
+        assert!(window.write(b"e").is_err());
+
+        assert_eq!(window.into_inner().into_inner(), b"01234ab789");
+    }
+}
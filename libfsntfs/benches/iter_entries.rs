@@ -0,0 +1,48 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::Criterion;
+use libbfio_rs::cache::CacheOptions;
+use libfsntfs_rs::volume::{AccessMode, Volume};
+use libfsntfs_rs::volume_options::VolumeOptions;
+use std::path::PathBuf;
+
+fn sample_volume_path() -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join("ntfs-img-kw-1.dd")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+fn read_all_entries(volume: &Volume) -> usize {
+    volume
+        .iter_entries()
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.get_name().ok())
+        .count()
+}
+
+fn iter_entries(c: &mut Criterion) {
+    c.bench_function("iter_entries", |b| {
+        b.iter(|| {
+            let volume = Volume::open(sample_volume_path(), AccessMode::Read).unwrap();
+            read_all_entries(&volume)
+        })
+    });
+
+    c.bench_function("iter_entries (cached)", |b| {
+        b.iter(|| {
+            let volume = VolumeOptions::new()
+                .cache(CacheOptions::new())
+                .open(sample_volume_path())
+                .unwrap();
+            read_all_entries(&volume)
+        })
+    });
+}
+
+criterion_group!(benches, iter_entries);
+criterion_main!(benches);
@@ -9,16 +9,26 @@ use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::os::raw::c_int;
+use std::sync::Arc;
 use std::{fmt, ptr};
 
+/// The size of the `$STANDARD_INFORMATION` of NTFS versions before 3.0.
+const SHORT_STANDARD_INFORMATION_SIZE: u64 = 48;
+
 #[repr(C)]
 pub struct __Attribute(isize);
 
 pub type AttributeRefMut = *mut __Attribute;
 pub type AttributeRef = *const __Attribute;
 
+/// Keeps the file entry of an attribute alive.
+enum FileEntryHolder<'a> {
+    Borrowed(&'a FileEntry<'a>),
+    Shared(Arc<FileEntry<'static>>),
+}
+
 #[repr(C)]
-pub struct Attribute<'a>(AttributeRefMut, &'a FileEntry<'a>);
+pub struct Attribute<'a>(AttributeRefMut, FileEntryHolder<'a>);
 
 impl<'a> AsTypeRef for Attribute<'a> {
     type Ref = AttributeRef;
@@ -43,7 +53,16 @@ impl<'a> AsTypeRef for Attribute<'a> {
 
 impl<'a> Attribute<'a> {
     pub fn wrap_ptr(file_entry: &'a FileEntry<'a>, ptr: AttributeRefMut) -> Self {
-        Attribute(ptr, file_entry)
+        Attribute(ptr, FileEntryHolder::Borrowed(file_entry))
+    }
+}
+
+impl Attribute<'static> {
+    pub(crate) fn wrap_shared_ptr(
+        file_entry: Arc<FileEntry<'static>>,
+        ptr: AttributeRefMut,
+    ) -> Self {
+        Attribute(ptr, FileEntryHolder::Shared(file_entry))
     }
 }
 
@@ -424,9 +443,10 @@ pub struct StandardInformation {
     pub access_time: Option<DateTime<Utc>>,
     pub entry_modification_time: Option<DateTime<Utc>>,
     pub file_attribute_flags: u32,
-    pub owner_identifier: u32,
-    pub security_descriptor_identifier: u32,
-    pub update_sequence_number: u32,
+    /// The owner, security and USN fields are only available in NTFS 3.0+.
+    pub owner_identifier: Option<u32>,
+    pub security_descriptor_identifier: Option<u32>,
+    pub update_sequence_number: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -442,11 +462,12 @@ pub struct FileName {
 
 #[derive(Debug, Clone)]
 pub struct Data {
+    pub name: String,
     // TOOD: parse flags
-    pub flags: u32,
+    pub flags: u16,
     pub vcn_range_first: u64,
     pub vcn_range_last: u64,
-    pub size: u32,
+    pub size: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -470,7 +491,11 @@ pub struct IndexAllocation {}
 #[derive(Debug, Clone)]
 pub struct Bitmap {}
 #[derive(Debug, Clone)]
-pub struct ReparsePoint {}
+pub struct ReparsePoint {
+    pub tag: u32,
+    pub substitute_name: Option<String>,
+    pub print_name: Option<String>,
+}
 #[derive(Debug, Clone)]
 pub struct ExtendedInformation {}
 #[derive(Debug, Clone)]
@@ -525,6 +550,9 @@ impl<'a> Attribute<'a> {
                     libfsntfs_file_name_attribute_get_parent_file_reference
                 )?;
 
+                let file_attribute_flags =
+                    get_u32_field!(self, libfsntfs_file_name_attribute_get_file_attribute_flags)?;
+
                 Ok(AttributeWithInformation::FileName(FileName {
                     name,
                     parent_file_reference,
@@ -532,7 +560,7 @@ impl<'a> Attribute<'a> {
                     modification_time,
                     access_time,
                     entry_modification_time,
-                    file_attribute_flags: 0,
+                    file_attribute_flags,
                 }))
             }
             AttributeType::StandardInformation => {
@@ -553,25 +581,94 @@ impl<'a> Attribute<'a> {
                     libfsntfs_standard_information_attribute_get_entry_modification_time
                 )?;
 
+                let file_attribute_flags = get_u32_field!(
+                    self,
+                    libfsntfs_standard_information_attribute_get_file_attribute_flags
+                )?;
+
+                // These are only available in NTFS 3.0+, older versions have a short attribute.
+                let is_long = self.get_data_size()? > SHORT_STANDARD_INFORMATION_SIZE;
+
+                let owner_identifier = if is_long {
+                    self.get_optional_u32(
+                        libfsntfs_standard_information_attribute_get_owner_identifier,
+                    )?
+                } else {
+                    None
+                };
+                let security_descriptor_identifier = if is_long {
+                    self.get_optional_u32(
+                        libfsntfs_standard_information_attribute_get_security_descriptor_identifier,
+                    )?
+                } else {
+                    None
+                };
+                let update_sequence_number = if is_long {
+                    self.get_optional_u64(
+                        libfsntfs_standard_information_attribute_get_update_sequence_number,
+                    )?
+                } else {
+                    None
+                };
+
                 Ok(AttributeWithInformation::StandardInformation(
                     StandardInformation {
                         creation_time,
                         modification_time,
                         access_time,
                         entry_modification_time,
-                        file_attribute_flags: 0,
-                        owner_identifier: 0,
-                        security_descriptor_identifier: 0,
-                        update_sequence_number: 0,
+                        file_attribute_flags,
+                        owner_identifier,
+                        security_descriptor_identifier,
+                        update_sequence_number,
                     },
                 ))
             }
-            AttributeType::Data => Ok(AttributeWithInformation::Data(Data {
-                flags: 0,
-                vcn_range_first: 0,
-                vcn_range_last: 0,
-                size: 0,
-            })),
+            AttributeType::Data => {
+                let mut flags = 0_u16;
+                let mut error = ptr::null_mut();
+
+                if unsafe {
+                    libfsntfs_attribute_get_data_flags(self.as_type_ref(), &mut flags, &mut error)
+                } != 1
+                {
+                    return Err(Error::try_from(error)?);
+                }
+
+                let mut vcn_range_first = 0_u64;
+                let mut vcn_range_last = 0_u64;
+                let mut error = ptr::null_mut();
+
+                // Resident data has no VCN range.
+                if unsafe {
+                    libfsntfs_attribute_get_data_vcn_range(
+                        self.as_type_ref(),
+                        &mut vcn_range_first,
+                        &mut vcn_range_last,
+                        &mut error,
+                    )
+                } == -1
+                {
+                    return Err(Error::try_from(error)?);
+                }
+
+                Ok(AttributeWithInformation::Data(Data {
+                    name: self.get_name()?,
+                    flags,
+                    vcn_range_first,
+                    vcn_range_last,
+                    size: self.get_data_size()?,
+                }))
+            }
+            AttributeType::ReparsePoint => {
+                let tag = get_u32_field!(self, libfsntfs_reparse_point_attribute_get_tag)?;
+
+                Ok(AttributeWithInformation::ReparsePoint(ReparsePoint {
+                    tag,
+                    substitute_name: self.get_reparse_point_substitute_name()?,
+                    print_name: self.get_reparse_point_print_name()?,
+                }))
+            }
 
             AttributeType::SecurityDescriptor => {
                 let descriptor = get_sized_bytes!(
@@ -591,6 +688,59 @@ impl<'a> Attribute<'a> {
         }
     }
 
+    /// Retrieves the data size.
+    pub fn get_data_size(&self) -> Result<u64, Error> {
+        get_u64_field!(self, libfsntfs_attribute_get_data_size)
+    }
+
+    /// Retrieves the reparse point substitute name, if available.
+    pub fn get_reparse_point_substitute_name(&self) -> Result<Option<String>, Error> {
+        get_optional_sized_utf8_string!(
+            self,
+            libfsntfs_reparse_point_attribute_get_utf8_substitute_name_size,
+            libfsntfs_reparse_point_attribute_get_utf8_substitute_name
+        )
+    }
+
+    /// Retrieves the reparse point print name, if available.
+    pub fn get_reparse_point_print_name(&self) -> Result<Option<String>, Error> {
+        get_optional_sized_utf8_string!(
+            self,
+            libfsntfs_reparse_point_attribute_get_utf8_print_name_size,
+            libfsntfs_reparse_point_attribute_get_utf8_print_name
+        )
+    }
+
+    /// Calls a getter which returns 0 if the value is not available.
+    fn get_optional_u32(
+        &self,
+        getter: unsafe extern "C" fn(AttributeRef, *mut u32, *mut LibfsntfsErrorRefMut) -> c_int,
+    ) -> Result<Option<u32>, Error> {
+        let mut value = 0_u32;
+        let mut error = ptr::null_mut();
+
+        match unsafe { getter(self.as_type_ref(), &mut value, &mut error) } {
+            1 => Ok(Some(value)),
+            0 => Ok(None),
+            _ => Err(Error::try_from(error)?),
+        }
+    }
+
+    /// Calls a getter which returns 0 if the value is not available.
+    fn get_optional_u64(
+        &self,
+        getter: unsafe extern "C" fn(AttributeRef, *mut u64, *mut LibfsntfsErrorRefMut) -> c_int,
+    ) -> Result<Option<u64>, Error> {
+        let mut value = 0_u64;
+        let mut error = ptr::null_mut();
+
+        match unsafe { getter(self.as_type_ref(), &mut value, &mut error) } {
+            1 => Ok(Some(value)),
+            0 => Ok(None),
+            _ => Err(Error::try_from(error)?),
+        }
+    }
+
     pub fn get_type(&self) -> Result<AttributeType, Error> {
         let mut type_as_num = 0_u32;
         let mut error = ptr::null_mut();
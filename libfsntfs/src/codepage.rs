@@ -0,0 +1,142 @@
+//! The codepage libfsntfs uses for narrow (ASCII and 8.3) strings.
+//!
+//! The codepage is a process-wide setting of libfsntfs, not a property of a volume.
+use crate::error::Error;
+use crate::ffi_error::LibfsntfsErrorRefMut;
+use std::convert::TryFrom;
+use std::os::raw::c_int;
+use std::ptr;
+
+extern "C" {
+    pub fn libfsntfs_get_codepage(codepage: *mut c_int, error: *mut LibfsntfsErrorRefMut) -> c_int;
+    pub fn libfsntfs_set_codepage(codepage: c_int, error: *mut LibfsntfsErrorRefMut) -> c_int;
+}
+
+/// The codepages supported by libfsntfs (see `libfsntfs/codepage.h`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codepage {
+    Ascii,
+    Iso8859_1,
+    Iso8859_2,
+    Iso8859_5,
+    Iso8859_15,
+    Koi8R,
+    Koi8U,
+    Windows874,
+    Windows932,
+    Windows936,
+    Windows949,
+    Windows950,
+    Windows1250,
+    Windows1251,
+    Windows1252,
+    Windows1253,
+    Windows1254,
+    Windows1255,
+    Windows1256,
+    Windows1257,
+    Windows1258,
+    /// Any other codepage number, which libfsntfs may reject.
+    Other(c_int),
+}
+
+impl Codepage {
+    pub fn as_int(&self) -> c_int {
+        match self {
+            Codepage::Ascii => 20127,
+            Codepage::Iso8859_1 => 28591,
+            Codepage::Iso8859_2 => 28592,
+            Codepage::Iso8859_5 => 28595,
+            Codepage::Iso8859_15 => 28605,
+            Codepage::Koi8R => 20866,
+            Codepage::Koi8U => 21866,
+            Codepage::Windows874 => 874,
+            Codepage::Windows932 => 932,
+            Codepage::Windows936 => 936,
+            Codepage::Windows949 => 949,
+            Codepage::Windows950 => 950,
+            Codepage::Windows1250 => 1250,
+            Codepage::Windows1251 => 1251,
+            Codepage::Windows1252 => 1252,
+            Codepage::Windows1253 => 1253,
+            Codepage::Windows1254 => 1254,
+            Codepage::Windows1255 => 1255,
+            Codepage::Windows1256 => 1256,
+            Codepage::Windows1257 => 1257,
+            Codepage::Windows1258 => 1258,
+            Codepage::Other(codepage) => *codepage,
+        }
+    }
+
+    pub fn from_int(codepage: c_int) -> Self {
+        match codepage {
+            20127 => Codepage::Ascii,
+            28591 => Codepage::Iso8859_1,
+            28592 => Codepage::Iso8859_2,
+            28595 => Codepage::Iso8859_5,
+            28605 => Codepage::Iso8859_15,
+            20866 => Codepage::Koi8R,
+            21866 => Codepage::Koi8U,
+            874 => Codepage::Windows874,
+            932 => Codepage::Windows932,
+            936 => Codepage::Windows936,
+            949 => Codepage::Windows949,
+            950 => Codepage::Windows950,
+            1250 => Codepage::Windows1250,
+            1251 => Codepage::Windows1251,
+            1252 => Codepage::Windows1252,
+            1253 => Codepage::Windows1253,
+            1254 => Codepage::Windows1254,
+            1255 => Codepage::Windows1255,
+            1256 => Codepage::Windows1256,
+            1257 => Codepage::Windows1257,
+            1258 => Codepage::Windows1258,
+            other => Codepage::Other(other),
+        }
+    }
+}
+
+/// Retrieves the codepage libfsntfs currently uses.
+pub fn get_codepage() -> Result<Codepage, Error> {
+    let mut codepage = 0;
+    let mut error = ptr::null_mut();
+
+    if unsafe { libfsntfs_get_codepage(&mut codepage, &mut error) } != 1 {
+        Err(Error::try_from(error)?)
+    } else {
+        Ok(Codepage::from_int(codepage))
+    }
+}
+
+/// Sets the codepage libfsntfs uses, for all the volumes in the process.
+pub fn set_codepage(codepage: Codepage) -> Result<(), Error> {
+    let mut error = ptr::null_mut();
+
+    if unsafe { libfsntfs_set_codepage(codepage.as_int(), &mut error) } != 1 {
+        Err(Error::try_from(error)?)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::lock_codepage;
+
+    #[test]
+    fn test_codepage_int_roundtrip() {
+        for codepage in &[Codepage::Ascii, Codepage::Windows1252, Codepage::Other(1)] {
+            assert_eq!(Codepage::from_int(codepage.as_int()), *codepage);
+        }
+    }
+
+    #[test]
+    fn test_set_codepage() {
+        let _codepage = lock_codepage();
+
+        set_codepage(Codepage::Windows1252).unwrap();
+
+        assert_eq!(get_codepage().unwrap(), Codepage::Windows1252);
+    }
+}
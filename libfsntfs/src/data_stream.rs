@@ -0,0 +1,201 @@
+//! Alternate data streams (named `$DATA` attributes) of file entries.
+use crate::error::Error;
+use crate::ffi_error::LibfsntfsErrorRefMut;
+use crate::file_entry::FileEntry;
+use crate::utils::signed_offset;
+use libfsntfs_sys::{off64_t, size64_t, SEEK_CUR, SEEK_END, SEEK_SET};
+use libyal_rs_common::ffi::AsTypeRef;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+#[repr(C)]
+pub struct __DataStream(isize);
+
+pub type DataStreamRefMut = *mut __DataStream;
+pub type DataStreamRef = *const __DataStream;
+
+/// A data stream borrows the file entry it was retrieved from.
+#[repr(C)]
+pub struct DataStream<'a>(DataStreamRefMut, PhantomData<&'a FileEntry<'a>>);
+
+impl<'a> AsTypeRef for DataStream<'a> {
+    type Ref = DataStreamRef;
+    type RefMut = DataStreamRefMut;
+
+    #[inline]
+    fn as_type_ref(&self) -> Self::Ref {
+        // https://users.rust-lang.org/t/is-it-ub-to-convert-t-to-mut-t/16238/4
+        self.0 as *const _
+    }
+
+    #[inline]
+    fn as_type_ref_mut(&mut self) -> Self::RefMut {
+        self.0
+    }
+
+    #[inline]
+    fn as_raw(&mut self) -> *mut Self::RefMut {
+        &mut self.0 as *mut _
+    }
+}
+
+impl<'a> DataStream<'a> {
+    pub fn wrap_ptr(_file_entry: &'a FileEntry<'a>, ptr: DataStreamRefMut) -> Self {
+        DataStream(ptr, PhantomData)
+    }
+}
+
+impl<'a> Drop for DataStream<'a> {
+    fn drop(&mut self) {
+        use log::trace;
+
+        let mut error = ptr::null_mut();
+
+        trace!("Calling `libfsntfs_data_stream_free`");
+
+        unsafe {
+            libfsntfs_data_stream_free(self.as_raw(), &mut error);
+        }
+
+        debug_assert!(error.is_null(), "`libfsntfs_data_stream_free` failed!");
+    }
+}
+
+impl<'a> Debug for DataStream<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("DataStream")
+            .field("Name", &self.get_name().unwrap_or_else(|_| "".to_string()))
+            .field("Size", &self.get_size().unwrap_or(0))
+            .finish()
+    }
+}
+
+extern "C" {
+    pub fn libfsntfs_data_stream_free(
+        data_stream: *mut DataStreamRefMut,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_data_stream_get_utf8_name_size(
+        data_stream: DataStreamRef,
+        utf8_string_size: *mut usize,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_data_stream_get_utf8_name(
+        data_stream: DataStreamRef,
+        utf8_string: *mut u8,
+        utf8_string_size: usize,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_data_stream_read_buffer(
+        data_stream: DataStreamRef,
+        buffer: *mut c_void,
+        buffer_size: usize,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> isize;
+    pub fn libfsntfs_data_stream_read_buffer_at_offset(
+        data_stream: DataStreamRef,
+        buffer: *mut c_void,
+        buffer_size: usize,
+        offset: off64_t,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> isize;
+    pub fn libfsntfs_data_stream_seek_offset(
+        data_stream: DataStreamRef,
+        offset: off64_t,
+        whence: c_int,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> off64_t;
+    pub fn libfsntfs_data_stream_get_size(
+        data_stream: DataStreamRef,
+        size: *mut size64_t,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+}
+
+impl<'a> DataStream<'a> {
+    pub fn get_name(&self) -> Result<String, Error> {
+        get_sized_utf8_string!(
+            self,
+            libfsntfs_data_stream_get_utf8_name_size,
+            libfsntfs_data_stream_get_utf8_name
+        )
+    }
+
+    pub fn get_size(&self) -> Result<u64, Error> {
+        get_u64_field!(self, libfsntfs_data_stream_get_size)
+    }
+
+    /// Reads data at a specific offset (this also moves the current offset).
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+        let offset = signed_offset(offset)?;
+        let mut error = ptr::null_mut();
+
+        let read_count = unsafe {
+            libfsntfs_data_stream_read_buffer_at_offset(
+                self.as_type_ref(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                offset,
+                &mut error,
+            )
+        };
+
+        if read_count <= -1 {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(read_count as usize)
+        }
+    }
+}
+
+impl<'a> Read for DataStream<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        let mut error = ptr::null_mut();
+
+        let read_count = unsafe {
+            libfsntfs_data_stream_read_buffer(
+                self.as_type_ref(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                &mut error,
+            )
+        };
+
+        if read_count <= -1 {
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
+        } else {
+            Ok(read_count as usize)
+        }
+    }
+}
+
+impl<'a> Seek for DataStream<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (signed_offset(offset)?, SEEK_SET),
+            SeekFrom::End(offset) => (offset, SEEK_END),
+            SeekFrom::Current(offset) => (offset, SEEK_CUR),
+        };
+
+        let mut error = ptr::null_mut();
+
+        let seek_pos = unsafe {
+            libfsntfs_data_stream_seek_offset(
+                self.as_type_ref(),
+                offset,
+                whence as c_int,
+                &mut error,
+            )
+        };
+
+        if seek_pos <= -1 {
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
+        } else {
+            Ok(seek_pos as u64)
+        }
+    }
+}
@@ -2,9 +2,11 @@ use libyal_rs_common::ffi::AsTypeRef;
 use crate::ffi_error::{LibfsntfsError, LibfsntfsErrorRef};
 use libfsntfs_sys::*;
 use failure::Fail;
+use libyal_rs_common::error::NativeError;
 use std::convert::TryFrom;
 use std::ffi::{c_void, FromBytesWithNulError, NulError};
 use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::os::raw::c_char;
@@ -24,7 +26,74 @@ pub enum Error {
     #[fail(display = "String contains NUL where is it not allowed: {}", _0)]
     StringContainsNul(#[cause] NulError),
     #[fail(display = "An FFI error has occurred: {}", _0)]
-    FFI(String),
+    FFI(#[cause] NativeError),
+    #[fail(display = "An IO error has occurred: {}", _0)]
+    Io(#[cause] std::io::Error),
+    #[fail(display = "Invalid partition table: {}", _0)]
+    InvalidPartitionTable(String),
+    #[fail(display = "File content is not available for entries of an MFT metadata file")]
+    ContentNotAvailable,
+    #[fail(display = "Invalid USN record: {}", _0)]
+    InvalidUsnRecord(String),
     #[fail(display = "An unexpected error has occurred: {}", _0)]
     Other(String),
 }
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::FFI(native) => native.into(),
+            Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::Other, e.to_string()),
+        }
+    }
+}
+
+/// Keeps the native error (and so its domain, code and `io::ErrorKind`) of libbfio errors.
+impl From<libbfio_rs::error::Error> for Error {
+    fn from(e: libbfio_rs::error::Error) -> Self {
+        use libbfio_rs::error::Error as BfioError;
+
+        match e {
+            BfioError::FailedToConvertDate(e) => Error::FailedToConvertDate(e),
+            BfioError::UnknownAttributeEnumVariant(variant) => {
+                Error::UnknownAttributeEnumVariant(variant)
+            }
+            BfioError::StringContainsInvalidUTF8(e) => Error::StringContainsInvalidUTF8(e),
+            BfioError::FailedToConvertFromBytes(e) => Error::FailedToConvertFromBytes(e),
+            BfioError::StringContainsNul(e) => Error::StringContainsNul(e),
+            BfioError::FailedToOpenFile(e) | BfioError::Io(e) => Error::Io(e),
+            BfioError::FFI(native) => Error::FFI(native),
+            BfioError::Other(message) => Error::Other(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libyal_rs_common::error::ErrorDomain;
+
+    #[test]
+    fn test_from_libbfio_error() {
+        let native = NativeError::new(ErrorDomain::Io, 6, vec!["access denied.".into()]);
+
+        match Error::from(libbfio_rs::error::Error::FFI(native.clone())) {
+            Error::FFI(e) => assert_eq!(e, native),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let not_found = io::Error::new(io::ErrorKind::NotFound, "no such file");
+
+        match Error::from(libbfio_rs::error::Error::FailedToOpenFile(not_found)) {
+            Error::Io(e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let native = libbfio_rs::error::Error::FFI(native);
+        assert_eq!(
+            io::Error::from(Error::from(native)).kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+}
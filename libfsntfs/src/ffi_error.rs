@@ -1,9 +1,11 @@
 use crate::error::Error;
+use libcerror_sys::libcerror_error_matches;
+use libyal_rs_common::error::NativeError;
 use libyal_rs_common::ffi::AsTypeRef;
 use libfsntfs_sys::FILE;
 use log::trace;
 use std::convert::TryFrom;
-use std::ffi::{c_void, CStr};
+use std::ffi::c_void;
 use std::fmt::{self, Display, Formatter};
 use std::mem;
 
@@ -66,6 +68,7 @@ impl Drop for LibfsntfsError {
     }
 }
 
+/// Takes ownership of the error, which is freed once it was read.
 impl TryFrom<*mut __LibfsntfsError> for Error {
     type Error = Error;
 
@@ -74,17 +77,18 @@ impl TryFrom<*mut __LibfsntfsError> for Error {
             return Err(Error::Other("Error pointer cannot be NULL".to_owned()));
         }
 
-        let mut buffer = vec![0; 1024];
+        let err = LibfsntfsError(err);
 
-        let retcode =
-            unsafe { libfsntfs_error_backtrace_sprint(err as *const _, buffer.as_mut_ptr(), buffer.len()) };
+        let native = NativeError::read(
+            |domain, code| unsafe { libcerror_error_matches(err.0 as _, domain, code) == 1 },
+            |string, size| unsafe {
+                libfsntfs_error_backtrace_sprint(err.as_type_ref(), string, size)
+            },
+        );
 
-        if retcode == -1 {
-            Err(Error::FFI("Failed to print error".to_owned()))
-        } else {
-            let repr = unsafe { CStr::from_ptr(buffer.as_ptr()) };
-            Ok(Error::FFI(repr.to_string_lossy().to_string()))
-        }
+        native
+            .map(Error::FFI)
+            .ok_or_else(|| Error::Other("Failed to print error".to_owned()))
     }
 }
 
@@ -93,6 +97,8 @@ mod tests {
     use crate::error::Error;
     use crate::fixtures::*;
     use crate::volume::{AccessMode, Volume};
+    use libyal_rs_common::error::ErrorDomain;
+    use std::io;
 
     #[test]
     fn test_error() {
@@ -100,16 +106,19 @@ mod tests {
         assert!(result.is_err());
 
         if let Err(e) = result {
-            if let Error::FFI(s) = e {
-                dbg!(&s);
+            if let Error::FFI(e) = e {
                 assert!(
-                    s.find("libfsntfs_volume_open").is_some(),
+                    e.message().find("libfsntfs_volume_open").is_some(),
                     "should contain FFI function name"
                 );
                 assert!(
-                    s.find("unable to open volume").is_some(),
+                    e.message().find("unable to open volume").is_some(),
                     "should contain message string"
                 );
+                assert!(e.frames().len() > 1, "should contain the whole backtrace");
+                assert_eq!(e.domain(), ErrorDomain::Io);
+                assert!(e.is_io());
+                assert_eq!(io::Error::from(Error::FFI(e)).kind(), io::ErrorKind::Other);
 
                 return;
             }
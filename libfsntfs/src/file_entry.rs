@@ -1,8 +1,12 @@
 use chrono::prelude::*;
 
 use crate::attribute::{Attribute, AttributeRef, AttributeRefMut};
+use crate::data_stream::{DataStream, DataStreamRefMut};
 use crate::error::Error;
 use crate::ffi_error::{LibfsntfsError, LibfsntfsErrorRef, LibfsntfsErrorRefMut};
+use crate::metadata::FileEntryMetadata;
+use crate::mft_metadata_file::MftMetadataFile;
+use crate::utils::signed_offset;
 use crate::volume::{Volume, VolumeRef};
 use libfsntfs_sys::{
     libfsntfs_attribute_t, libfsntfs_data_stream_t, off64_t, size64_t, SEEK_CUR, SEEK_END, SEEK_SET,
@@ -15,7 +19,9 @@ use std::fs::read;
 use std::io::{BufRead, Read, Seek, SeekFrom};
 use std::marker::PhantomData;
 use std::option::Iter;
+use std::ops::Deref;
 use std::os::raw::c_int;
+use std::sync::Arc;
 use std::{fmt, io, mem, ptr};
 
 #[repr(C)]
@@ -24,8 +30,25 @@ pub struct __FileEntry(isize);
 pub type FileEntryRefMut = *mut __FileEntry;
 pub type FileEntryRef = *const __FileEntry;
 
+/// Keeps the volume (or the MFT metadata file) of a file entry alive.
+pub(crate) enum VolumeHolder<'a> {
+    Borrowed(&'a Volume),
+    Shared(Arc<Volume>),
+    MftMetadataFile(&'a MftMetadataFile),
+}
+
+impl<'a> Clone for VolumeHolder<'a> {
+    fn clone(&self) -> Self {
+        match self {
+            VolumeHolder::Borrowed(volume) => VolumeHolder::Borrowed(volume),
+            VolumeHolder::Shared(volume) => VolumeHolder::Shared(volume.clone()),
+            VolumeHolder::MftMetadataFile(file) => VolumeHolder::MftMetadataFile(file),
+        }
+    }
+}
+
 #[repr(C)]
-pub struct FileEntry<'a>(FileEntryRefMut, &'a Volume);
+pub struct FileEntry<'a>(FileEntryRefMut, VolumeHolder<'a>);
 
 impl<'a> AsTypeRef for FileEntry<'a> {
     type Ref = FileEntryRef;
@@ -50,7 +73,22 @@ impl<'a> AsTypeRef for FileEntry<'a> {
 
 impl<'a> FileEntry<'a> {
     pub fn wrap_ptr(volume: &'a Volume, ptr: FileEntryRefMut) -> Self {
-        FileEntry(ptr, volume)
+        FileEntry(ptr, VolumeHolder::Borrowed(volume))
+    }
+
+    pub(crate) fn wrap_mft_metadata_file_ptr(
+        file: &'a MftMetadataFile,
+        ptr: FileEntryRefMut,
+    ) -> Self {
+        FileEntry(ptr, VolumeHolder::MftMetadataFile(file))
+    }
+
+    /// The content of entries of an MFT metadata file is not part of the file.
+    fn check_content_available(&self) -> Result<(), Error> {
+        match self.1 {
+            VolumeHolder::MftMetadataFile(_) => Err(Error::ContentNotAvailable),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -420,6 +458,21 @@ impl<'a: 'b, 'b> Iterator for IterSubEntries<'a, 'b> {
 
 impl<'a> Read for FileEntry<'a> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        self.read_buffer(buf)
+    }
+}
+
+impl<'a> Seek for FileEntry<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+        self.seek_offset(pos)
+    }
+}
+
+impl<'a> FileEntry<'a> {
+    fn read_buffer(&self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        self.check_content_available()
+            .map_err(io::Error::from)?;
+
         let mut error = ptr::null_mut();
         let read_count = unsafe {
             libfsntfs_file_entry_read_buffer(
@@ -431,32 +484,23 @@ impl<'a> Read for FileEntry<'a> {
         };
 
         if read_count <= -1 {
-            let ffi_err = Error::try_from(error);
-
-            let io_err = match ffi_err {
-                Ok(e) => io::Error::new(io::ErrorKind::Other, format!("{}", e)),
-                Err(e) => io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("error while getting error information"),
-                ),
-            };
-
-            Err(io_err)
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
         } else {
             Ok(read_count as usize)
         }
     }
-}
 
-impl<'a> Seek for FileEntry<'a> {
-    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+    fn seek_offset(&self, pos: SeekFrom) -> Result<u64, io::Error> {
+        self.check_content_available()
+            .map_err(io::Error::from)?;
+
         let mut error = ptr::null_mut();
 
         let seek_pos = match pos {
             SeekFrom::Start(offset) => unsafe {
                 libfsntfs_file_entry_seek_offset(
                     self.as_type_ref(),
-                    offset as i64,
+                    signed_offset(offset)?,
                     SEEK_SET as i32,
                     &mut error,
                 )
@@ -480,17 +524,7 @@ impl<'a> Seek for FileEntry<'a> {
         };
 
         if seek_pos <= -1 {
-            let ffi_err = Error::try_from(error);
-
-            let io_err = match ffi_err {
-                Ok(e) => io::Error::new(io::ErrorKind::Other, format!("{}", e)),
-                Err(e) => io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("error while getting error information"),
-                ),
-            };
-
-            Err(io_err)
+            Err(Error::try_from(error).unwrap_or_else(|e| e).into())
         } else {
             Ok(seek_pos as u64)
         }
@@ -503,6 +537,30 @@ impl<'a> FileEntry<'a> {
         unimplemented!();
     }
 
+    /// Reads data at a specific offset (this also moves the current offset).
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+        self.check_content_available()?;
+
+        let offset = signed_offset(offset)?;
+        let mut error = ptr::null_mut();
+
+        let read_count = unsafe {
+            libfsntfs_file_entry_read_buffer_at_offset(
+                self.as_type_ref(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                offset,
+                &mut error,
+            )
+        };
+
+        if read_count <= -1 {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(read_count as usize)
+        }
+    }
+
     pub fn get_size(&self) -> Result<u64, Error> {
         let mut size = 0;
         let mut error = ptr::null_mut();
@@ -516,13 +574,58 @@ impl<'a> FileEntry<'a> {
     }
 
     /// Retrieves a specific alternate data stream.
-    pub fn get_alternate_data_stream(&self, alternate_data_stream_index: isize) {
-        unimplemented!();
+    /// Fails with `Error::ContentNotAvailable` for entries of an MFT metadata file.
+    pub fn get_alternate_data_stream(
+        &self,
+        alternate_data_stream_index: c_int,
+    ) -> Result<DataStream, Error> {
+        self.check_content_available()?;
+
+        let mut data_stream = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_file_entry_get_alternate_data_stream_by_index(
+                self.as_type_ref(),
+                alternate_data_stream_index,
+                &mut data_stream,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(DataStream::wrap_ptr(self, data_stream as DataStreamRefMut))
+        }
     }
 
     /// Retrieves an alternate data stream specified by the name.
-    pub fn get_alternate_data_stream_by_name(&self, name: isize) {
-        unimplemented!();
+    /// Fails with `Error::ContentNotAvailable` for entries of an MFT metadata file.
+    pub fn get_alternate_data_stream_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Option<DataStream>, Error> {
+        self.check_content_available()?;
+
+        let mut data_stream = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        match unsafe {
+            libfsntfs_file_entry_get_alternate_data_stream_by_utf8_name(
+                self.as_type_ref(),
+                name.as_ptr(),
+                name.len(),
+                &mut data_stream,
+                &mut error,
+            )
+        } {
+            1 => Ok(Some(DataStream::wrap_ptr(
+                self,
+                data_stream as DataStreamRefMut,
+            ))),
+            0 => Ok(None),
+            _ => Err(Error::try_from(error)?),
+        }
     }
 
     pub fn iter_attributes(&self) -> Result<IterAttributes, Error> {
@@ -564,6 +667,12 @@ impl<'a> FileEntry<'a> {
     }
 
     pub fn get_attribute_by_index(&self, attribute_index: i32) -> Result<Attribute, Error> {
+        let attribute = self.get_attribute_ptr_by_index(attribute_index)?;
+
+        Ok(Attribute::wrap_ptr(self, attribute))
+    }
+
+    fn get_attribute_ptr_by_index(&self, attribute_index: i32) -> Result<AttributeRefMut, Error> {
         let mut attribute = ptr::null_mut();
         let mut error = ptr::null_mut();
 
@@ -578,7 +687,7 @@ impl<'a> FileEntry<'a> {
         {
             Err(Error::try_from(error)?)
         } else {
-            Ok(Attribute::wrap_ptr(self, attribute))
+            Ok(attribute)
         }
     }
 
@@ -605,7 +714,7 @@ impl<'a> FileEntry<'a> {
         {
             Err(Error::try_from(error)?)
         } else {
-            Ok(FileEntry::wrap_ptr(self.1, sub_entry))
+            Ok(FileEntry(sub_entry, self.1.clone()))
         }
     }
 
@@ -663,8 +772,10 @@ impl<'a> FileEntry<'a> {
         }
     }
 
-    pub fn get_base_record_file_reference(&self) {
-        unimplemented!();
+    /// Retrieves the base record file reference.
+    /// This differs from the file reference for MFT extension records.
+    pub fn get_base_record_file_reference(&self) -> Result<u64, Error> {
+        get_u64_field!(self, libfsntfs_file_entry_get_base_record_file_reference)
     }
 
     pub fn get_creation_time(&self) {
@@ -691,8 +802,9 @@ impl<'a> FileEntry<'a> {
         unimplemented!();
     }
 
-    pub fn get_journal_sequence_number(&self) {
-        unimplemented!();
+    /// Retrieves the journal sequence number.
+    pub fn get_journal_sequence_number(&self) -> Result<u64, Error> {
+        get_u64_field!(self, libfsntfs_file_entry_get_journal_sequence_number)
     }
 
     pub fn get_modification_time(&self) {
@@ -703,16 +815,44 @@ impl<'a> FileEntry<'a> {
         unimplemented!();
     }
 
-    pub fn get_name_attribute_index(&self) {
-        unimplemented!();
+    /// Retrieves the index of the `$FILE_NAME` attribute used for the name, if there is one.
+    pub fn get_name_attribute_index(&self) -> Result<Option<c_int>, Error> {
+        let mut attribute_index = 0;
+        let mut error = ptr::null_mut();
+
+        match unsafe {
+            libfsntfs_file_entry_get_name_attribute_index(
+                self.as_type_ref(),
+                &mut attribute_index,
+                &mut error,
+            )
+        } {
+            1 => Ok(Some(attribute_index)),
+            0 => Ok(None),
+            _ => Err(Error::try_from(error)?),
+        }
     }
 
     pub fn get_name_by_attribute_index(&self, attribute_index: isize) {
         unimplemented!();
     }
 
-    pub fn get_number_of_alternate_data_streams(&self) {
-        unimplemented!();
+    pub fn get_number_of_alternate_data_streams(&self) -> Result<c_int, Error> {
+        let mut number_of_alternate_data_streams = 0;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_file_entry_get_number_of_alternate_data_streams(
+                self.as_type_ref(),
+                &mut number_of_alternate_data_streams,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(number_of_alternate_data_streams)
+        }
     }
 
     pub fn get_number_of_extents(&self) {
@@ -735,20 +875,162 @@ impl<'a> FileEntry<'a> {
         unimplemented!();
     }
 
-    pub fn has_alternate_data_stream_by_name(&self, name: isize) {
-        unimplemented!();
+    pub fn has_alternate_data_stream_by_name(&self, name: &str) -> Result<bool, Error> {
+        let mut error = ptr::null_mut();
+
+        match unsafe {
+            libfsntfs_file_entry_has_alternate_data_stream_by_utf8_name(
+                self.as_type_ref(),
+                name.as_ptr(),
+                name.len(),
+                &mut error,
+            )
+        } {
+            1 => Ok(true),
+            0 => Ok(false),
+            _ => Err(Error::try_from(error)?),
+        }
     }
 
     pub fn has_default_data_stream(&self) {
         unimplemented!();
     }
 
-    pub fn has_directory_entries_index(&self) {
-        unimplemented!();
+    pub fn has_directory_entries_index(&self) -> Result<bool, Error> {
+        get_bool_field!(self, libfsntfs_file_entry_has_directory_entries_index)
     }
 
-    pub fn is_empty(&self) {
-        unimplemented!();
+    /// Determines if the file entry is empty (the MFT entry was never used).
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        get_bool_field!(self, libfsntfs_file_entry_is_empty)
+    }
+
+    /// Determines if the file entry is allocated (in use).
+    pub fn is_allocated(&self) -> Result<bool, Error> {
+        get_bool_field!(self, libfsntfs_file_entry_is_allocated)
+    }
+
+    /// Loads the metadata of the file entry into an owned `FileEntryMetadata`,
+    /// which does not borrow the volume.
+    pub fn metadata(&self) -> Result<FileEntryMetadata, Error> {
+        FileEntryMetadata::from_file_entry(self)
+    }
+}
+
+/// A reference counted file entry of a `SharedVolume`.
+///
+/// The entry keeps the volume alive, and attributes retrieved from it keep the entry alive,
+/// so neither is bound to the lifetime of a borrow.
+/// Clones share the same underlying file entry (including the current offset used by `Read`).
+#[derive(Clone)]
+pub struct SharedFileEntry(Arc<FileEntry<'static>>);
+
+impl Deref for SharedFileEntry {
+    type Target = FileEntry<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Debug for SharedFileEntry {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        self.0.fmt(f)
+    }
+}
+
+impl Read for SharedFileEntry {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        self.0.read_buffer(buf)
+    }
+}
+
+impl Seek for SharedFileEntry {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+        self.0.seek_offset(pos)
+    }
+}
+
+pub struct IterSharedAttributes {
+    handle: SharedFileEntry,
+    num_attributes: u32,
+    idx: u32,
+}
+
+impl Iterator for IterSharedAttributes {
+    type Item = Result<Attribute<'static>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx < self.num_attributes {
+            let attr = self.handle.get_attribute_by_index(self.idx as i32);
+            self.idx += 1;
+
+            return Some(attr);
+        }
+
+        None
+    }
+}
+
+pub struct IterSharedSubEntries {
+    handle: SharedFileEntry,
+    num_sub_entries: u32,
+    idx: u32,
+}
+
+impl Iterator for IterSharedSubEntries {
+    type Item = Result<SharedFileEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx < self.num_sub_entries {
+            let sub_entry = self.handle.get_sub_file_entry(self.idx as i32);
+            self.idx += 1;
+
+            return Some(sub_entry);
+        }
+
+        None
+    }
+}
+
+impl SharedFileEntry {
+    pub(crate) fn wrap_ptr(volume: Arc<Volume>, ptr: FileEntryRefMut) -> Self {
+        SharedFileEntry(Arc::new(FileEntry(ptr, VolumeHolder::Shared(volume))))
+    }
+
+    pub fn iter_attributes(&self) -> Result<IterSharedAttributes, Error> {
+        let number_of_attributes = self.get_number_of_attributes()? as u32;
+
+        Ok(IterSharedAttributes {
+            handle: self.clone(),
+            num_attributes: number_of_attributes,
+            idx: 0,
+        })
+    }
+
+    pub fn iter_sub_entries(&self) -> Result<IterSharedSubEntries, Error> {
+        let number_sub_entries = self.get_number_of_sub_file_entries()? as u32;
+
+        Ok(IterSharedSubEntries {
+            handle: self.clone(),
+            num_sub_entries: number_sub_entries,
+            idx: 0,
+        })
+    }
+
+    pub fn get_attribute_by_index(
+        &self,
+        attribute_index: i32,
+    ) -> Result<Attribute<'static>, Error> {
+        let attribute = self.0.get_attribute_ptr_by_index(attribute_index)?;
+
+        Ok(Attribute::wrap_shared_ptr(self.0.clone(), attribute))
+    }
+
+    pub fn get_sub_file_entry(&self, sub_file_entry_index: i32) -> Result<SharedFileEntry, Error> {
+        Ok(SharedFileEntry(Arc::new(
+            self.0.get_sub_file_entry(sub_file_entry_index)?,
+        )))
     }
 }
 
@@ -774,6 +1056,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_alternate_data_streams() {
+        let volume = sample_volume().unwrap();
+        // The security descriptors are in the `$SDS` stream of `$Secure`.
+        let secure = volume.get_file_entry_by_mft_idx(9).unwrap();
+
+        assert!(secure.get_number_of_alternate_data_streams().unwrap() > 0);
+        assert!(secure.has_alternate_data_stream_by_name("$SDS").unwrap());
+        assert!(secure
+            .get_alternate_data_stream_by_name("missing")
+            .unwrap()
+            .is_none());
+
+        let mut stream = secure
+            .get_alternate_data_stream_by_name("$SDS")
+            .unwrap()
+            .unwrap();
+        let mut buf = vec![0; 16];
+
+        assert_eq!(stream.get_name().unwrap(), "$SDS");
+        assert!(stream.get_size().unwrap() > 0);
+        assert_eq!(stream.read(&mut buf).unwrap(), 16);
+        assert_eq!(stream.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert_eq!(stream.read_at(&mut buf, 0).unwrap(), 16);
+    }
+
     #[test]
     fn test_read() {
         let volume = sample_volume().unwrap();
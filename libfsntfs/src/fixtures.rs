@@ -1,16 +1,44 @@
 use crate::attribute::{Attribute, AttributeType};
+use crate::codepage::{get_codepage, set_codepage, Codepage};
 use crate::error::Error;
 use crate::file_entry::FileEntry;
 use crate::volume::{AccessMode, Volume};
 use env_logger;
 use lazy_static::lazy_static;
 use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
 use libbfio_rs::handle::{Handle, LibbfioAccessFlags};
 
 lazy_static! {
     static ref LOGGER_INIT: () = {
         env_logger::init();
     };
+    static ref CODEPAGE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Serializes the tests which set the process-wide codepage, and restores the previous
+/// codepage when dropped.
+pub struct CodepageGuard {
+    previous: Codepage,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Drop for CodepageGuard {
+    fn drop(&mut self) {
+        let _ = set_codepage(self.previous);
+    }
+}
+
+pub fn lock_codepage() -> CodepageGuard {
+    // A test which failed while holding the lock still restored the codepage.
+    let lock = CODEPAGE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    CodepageGuard {
+        previous: get_codepage().unwrap(),
+        _lock: lock,
+    }
 }
 
 pub fn sample_volume_path() -> String {
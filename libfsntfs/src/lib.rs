@@ -6,11 +6,19 @@
 extern crate libyal_rs_common;
 
 pub mod attribute;
+pub mod codepage;
+pub mod data_stream;
 pub mod error;
 pub mod ffi_error;
 pub mod file_entry;
+pub mod metadata;
+pub mod mft_metadata_file;
+pub mod notify;
+pub mod partition;
+pub mod usn;
 mod utils;
 pub mod volume;
+pub mod volume_options;
 
 #[cfg(test)]
 mod fixtures;
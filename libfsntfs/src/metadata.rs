@@ -0,0 +1,189 @@
+//! An owned snapshot of a `FileEntry`'s metadata.
+//!
+//! Unlike `FileEntry` and `Attribute`, `FileEntryMetadata` does not borrow the volume,
+//! so it can be stored, cached or sent to other threads.
+use crate::attribute::{AttributeType, AttributeWithInformation, FileName, ReparsePoint};
+use crate::error::Error;
+use crate::file_entry::FileEntry;
+use crate::volume::{mft_entry_index_from_reference, MftEntryIndex};
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlternateDataStreamInfo {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileEntryMetadata {
+    pub file_reference: u64,
+    pub base_record_file_reference: u64,
+    pub parent_file_reference: Option<u64>,
+    pub journal_sequence_number: u64,
+    pub is_allocated: bool,
+    pub has_directory_entries_index: bool,
+    /// The name from the `$FILE_NAME` attribute libfsntfs considers the primary one.
+    pub name: Option<String>,
+    /// All the `$FILE_NAME` attributes of the entry (including DOS 8.3 names).
+    pub file_names: Vec<FileName>,
+    pub creation_time: Option<DateTime<Utc>>,
+    pub modification_time: Option<DateTime<Utc>>,
+    pub access_time: Option<DateTime<Utc>>,
+    pub entry_modification_time: Option<DateTime<Utc>>,
+    pub file_attribute_flags: u32,
+    /// The size of the default data stream.
+    pub size: u64,
+    pub alternate_data_streams: Vec<AlternateDataStreamInfo>,
+    pub reparse_point: Option<ReparsePoint>,
+    pub owner_identifier: Option<u32>,
+    pub security_descriptor_identifier: Option<u32>,
+}
+
+impl FileEntryMetadata {
+    /// Loads the metadata with a single pass over the attributes of the entry.
+    /// Timestamps, flags and the security id come from `$STANDARD_INFORMATION`,
+    /// so they are not available for entries without one (like extension records).
+    pub(crate) fn from_file_entry(entry: &FileEntry) -> Result<Self, Error> {
+        let mut standard_information = None;
+        let mut file_names = vec![];
+        let mut name_idx = None;
+        let mut alternate_data_streams: Vec<AlternateDataStreamInfo> = vec![];
+        let mut reparse_point = None;
+
+        let name_attribute_index = entry.get_name_attribute_index()?;
+
+        for (attribute_index, attribute) in entry.iter_attributes()?.enumerate() {
+            let attribute = attribute?;
+
+            match attribute.get_type()? {
+                AttributeType::StandardInformation => {
+                    if let AttributeWithInformation::StandardInformation(info) =
+                        attribute.get_data()?
+                    {
+                        standard_information = Some(info);
+                    }
+                }
+                AttributeType::FileName => {
+                    if let AttributeWithInformation::FileName(file_name) = attribute.get_data()? {
+                        if name_attribute_index == Some(attribute_index as i32) {
+                            name_idx = Some(file_names.len());
+                        }
+
+                        file_names.push(file_name);
+                    }
+                }
+                AttributeType::Data => {
+                    let name = attribute.get_name()?;
+
+                    // The unnamed stream is the default data stream.
+                    // A non-resident stream can be split across several attributes,
+                    // only the first one holds the size of the data.
+                    if !name.is_empty() && !alternate_data_streams.iter().any(|s| s.name == name) {
+                        alternate_data_streams.push(AlternateDataStreamInfo {
+                            name,
+                            size: attribute.get_data_size()?,
+                        });
+                    }
+                }
+                AttributeType::ReparsePoint => {
+                    if let AttributeWithInformation::ReparsePoint(info) = attribute.get_data()? {
+                        reparse_point = Some(info);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let name = name_idx.map(|idx| &file_names[idx]);
+
+        Ok(FileEntryMetadata {
+            file_reference: entry.get_file_reference()?,
+            base_record_file_reference: entry.get_base_record_file_reference()?,
+            parent_file_reference: name.map(|n| n.parent_file_reference),
+            journal_sequence_number: entry.get_journal_sequence_number()?,
+            is_allocated: entry.is_allocated()?,
+            has_directory_entries_index: entry.has_directory_entries_index()?,
+            name: name.map(|n| n.name.clone()),
+            creation_time: standard_information
+                .as_ref()
+                .and_then(|info| info.creation_time),
+            modification_time: standard_information
+                .as_ref()
+                .and_then(|info| info.modification_time),
+            access_time: standard_information
+                .as_ref()
+                .and_then(|info| info.access_time),
+            entry_modification_time: standard_information
+                .as_ref()
+                .and_then(|info| info.entry_modification_time),
+            file_attribute_flags: standard_information
+                .as_ref()
+                .map(|info| info.file_attribute_flags)
+                .unwrap_or(0),
+            size: entry.get_size()?,
+            owner_identifier: standard_information
+                .as_ref()
+                .and_then(|info| info.owner_identifier),
+            security_descriptor_identifier: standard_information
+                .as_ref()
+                .and_then(|info| info.security_descriptor_identifier),
+            file_names,
+            alternate_data_streams,
+            reparse_point,
+        })
+    }
+
+    /// The MFT entry index of the entry (the file reference without the sequence number).
+    pub fn mft_entry_index(&self) -> MftEntryIndex {
+        mft_entry_index_from_reference(self.file_reference)
+    }
+
+    /// Returns true if the entry is an MFT extension record of another entry.
+    pub fn is_extension_record(&self) -> bool {
+        self.base_record_file_reference != 0
+            && self.base_record_file_reference != self.file_reference
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_metadata_of_mft() {
+        let metadata = {
+            let volume = sample_volume().unwrap();
+            let entry = volume.get_file_entry_by_mft_idx(0).unwrap();
+            entry.metadata().unwrap()
+        };
+
+        // The volume is dropped by now.
+        assert_eq!(metadata.mft_entry_index(), 0);
+        assert_eq!(metadata.name, Some("$MFT".to_string()));
+        assert_eq!(
+            metadata
+                .parent_file_reference
+                .map(mft_entry_index_from_reference),
+            Some(5)
+        );
+        assert!(metadata.is_allocated);
+        assert!(!metadata.is_extension_record());
+        assert!(metadata.creation_time.is_some());
+        // The sample is an NTFS 3.1 volume, which has the owner and security identifiers.
+        assert!(metadata.owner_identifier.is_some());
+        assert!(metadata.security_descriptor_identifier.is_some());
+        assert!(metadata.size > 0);
+    }
+
+    #[test]
+    fn test_metadata_matches_file_entry() {
+        let volume = sample_volume().unwrap();
+        let entry = file_entry(&volume).unwrap();
+        let metadata = entry.metadata().unwrap();
+
+        assert_eq!(metadata.name, Some(entry.get_name().unwrap()));
+        assert_eq!(metadata.size, entry.get_size().unwrap());
+        assert_eq!(metadata.file_reference, entry.get_file_reference().unwrap());
+    }
+}
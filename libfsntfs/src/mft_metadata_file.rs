@@ -0,0 +1,379 @@
+//! Reads the file entries of an extracted `$MFT`, without the rest of the volume.
+//!
+//! The entries have all their metadata and attributes,
+//! but their content is not available since it lives outside of the `$MFT`.
+use crate::error::Error;
+use crate::ffi_error::LibfsntfsErrorRefMut;
+use crate::file_entry::{FileEntry, FileEntryRefMut};
+use crate::volume::{AccessMode, MftEntryIndex};
+use libbfio_rs::handle::{Handle, HandleRef};
+use libyal_rs_common::ffi::AsTypeRef;
+use log::error;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+#[repr(C)]
+pub struct __MftMetadataFile(isize);
+
+pub type MftMetadataFileRefMut = *mut __MftMetadataFile;
+pub type MftMetadataFileRef = *const __MftMetadataFile;
+
+/// Like `Volume`, the file may own the libbfio handle it was opened from.
+#[repr(C)]
+pub struct MftMetadataFile(MftMetadataFileRefMut, Option<Handle>);
+
+impl AsTypeRef for MftMetadataFile {
+    type Ref = MftMetadataFileRef;
+    type RefMut = MftMetadataFileRefMut;
+
+    #[inline]
+    fn as_type_ref(&self) -> Self::Ref {
+        // https://users.rust-lang.org/t/is-it-ub-to-convert-t-to-mut-t/16238/4
+        self.0 as *const _
+    }
+
+    fn as_type_ref_mut(&mut self) -> Self::RefMut {
+        self.0
+    }
+
+    fn as_raw(&mut self) -> *mut Self::RefMut {
+        &mut self.0 as *mut _
+    }
+}
+
+// See `Volume`.
+unsafe impl Send for MftMetadataFile {}
+
+impl Drop for MftMetadataFile {
+    fn drop(&mut self) {
+        let mut error = ptr::null_mut();
+
+        if unsafe { libfsntfs_mft_metadata_file_close(self.as_type_ref(), &mut error) } != 1 {
+            error!("`libfsntfs_mft_metadata_file_close` failed!");
+        }
+
+        let mut error = ptr::null_mut();
+        if unsafe { libfsntfs_mft_metadata_file_free(self.as_raw(), &mut error) } != 1 {
+            panic!("`libfsntfs_mft_metadata_file_free` failed!");
+        }
+    }
+}
+
+extern "C" {
+    pub fn libfsntfs_mft_metadata_file_initialize(
+        mft_metadata_file: *mut MftMetadataFileRefMut,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_free(
+        mft_metadata_file: *mut MftMetadataFileRefMut,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_open(
+        mft_metadata_file: MftMetadataFileRef,
+        filename: *const c_char,
+        access_flags: c_int,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_open_file_io_handle(
+        mft_metadata_file: MftMetadataFileRef,
+        file_io_handle: HandleRef,
+        access_flags: c_int,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_close(
+        mft_metadata_file: MftMetadataFileRef,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_get_utf8_volume_name_size(
+        mft_metadata_file: MftMetadataFileRef,
+        utf8_string_size: *mut usize,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_get_utf8_volume_name(
+        mft_metadata_file: MftMetadataFileRef,
+        utf8_string: *mut u8,
+        utf8_string_size: usize,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_get_volume_version(
+        mft_metadata_file: MftMetadataFileRef,
+        major_version: *mut u8,
+        minor_version: *mut u8,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_get_number_of_file_entries(
+        mft_metadata_file: MftMetadataFileRef,
+        number_of_file_entries: *mut u64,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_mft_metadata_file_get_file_entry_by_index(
+        mft_metadata_file: MftMetadataFileRef,
+        mft_entry_index: u64,
+        file_entry: *mut FileEntryRefMut,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+}
+
+pub struct IterMftFileEntries<'a> {
+    handle: &'a MftMetadataFile,
+    number_of_file_entries: usize,
+    idx: usize,
+}
+
+impl<'a> Iterator for IterMftFileEntries<'a> {
+    type Item = Result<FileEntry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx < self.number_of_file_entries {
+            let entry = self
+                .handle
+                .get_file_entry_by_mft_idx(self.idx as MftEntryIndex);
+            self.idx += 1;
+
+            return Some(entry);
+        }
+
+        None
+    }
+}
+
+impl MftMetadataFile {
+    fn initialize() -> Result<Self, Error> {
+        let mut handle = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        if unsafe { libfsntfs_mft_metadata_file_initialize(&mut handle, &mut error) } != 1 {
+            return Err(Error::try_from(error)?);
+        }
+
+        Ok(MftMetadataFile(handle, None))
+    }
+
+    /// Opens an extracted `$MFT` by filename.
+    pub fn open(filename: impl AsRef<str>) -> Result<Self, Error> {
+        let c_string = CString::new(filename.as_ref()).map_err(Error::StringContainsNul)?;
+
+        let file = MftMetadataFile::initialize()?;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_mft_metadata_file_open(
+                file.as_type_ref(),
+                c_string.as_ptr(),
+                AccessMode::Read.as_flag() as c_int,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(file)
+        }
+    }
+
+    /// Opens an extracted `$MFT` using a libbfio handle, which is kept alive as long as the file.
+    pub fn open_file_object(file_handle: Handle) -> Result<Self, Error> {
+        let mut file = MftMetadataFile::initialize()?;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_mft_metadata_file_open_file_io_handle(
+                file.as_type_ref(),
+                file_handle.as_type_ref(),
+                AccessMode::Read.as_flag() as c_int,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            file.1 = Some(file_handle);
+            Ok(file)
+        }
+    }
+
+    /// Retrieves the name of the volume, from the `$Volume` entry.
+    pub fn get_volume_name(&self) -> Result<String, Error> {
+        get_sized_utf8_string!(
+            self,
+            libfsntfs_mft_metadata_file_get_utf8_volume_name_size,
+            libfsntfs_mft_metadata_file_get_utf8_volume_name
+        )
+    }
+
+    /// Retrieves the (major, minor) NTFS version of the volume, from the `$Volume` entry.
+    pub fn get_volume_version(&self) -> Result<(u8, u8), Error> {
+        let mut major_version = 0_u8;
+        let mut minor_version = 0_u8;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_mft_metadata_file_get_volume_version(
+                self.as_type_ref(),
+                &mut major_version,
+                &mut minor_version,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok((major_version, minor_version))
+        }
+    }
+
+    pub fn get_number_of_file_entries(&self) -> Result<usize, Error> {
+        let mut number_of_file_entries = 0;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_mft_metadata_file_get_number_of_file_entries(
+                self.as_type_ref(),
+                &mut number_of_file_entries,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(number_of_file_entries as usize)
+        }
+    }
+
+    /// Retrieves the file entry of a specific MFT entry index.
+    /// Reading its content fails with `Error::ContentNotAvailable`.
+    pub fn get_file_entry_by_mft_idx(&self, idx: MftEntryIndex) -> Result<FileEntry, Error> {
+        let mut file_entry = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_mft_metadata_file_get_file_entry_by_index(
+                self.as_type_ref(),
+                idx,
+                &mut file_entry,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(FileEntry::wrap_mft_metadata_file_ptr(self, file_entry))
+        }
+    }
+
+    pub fn iter_entries(&self) -> Result<IterMftFileEntries, Error> {
+        Ok(IterMftFileEntries {
+            handle: self,
+            number_of_file_entries: self.get_number_of_file_entries()?,
+            idx: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+    use libbfio_rs::handle::LibbfioAccessFlags;
+    use std::fs;
+    use std::io::Read;
+    use tempdir::TempDir;
+
+    /// Extracts the `$MFT` of the sample volume.
+    fn extract_mft(dir: &TempDir) -> String {
+        let volume = sample_volume().unwrap();
+        let mut mft = volume.get_file_entry_by_mft_idx(0).unwrap();
+
+        let mut data = vec![];
+        mft.read_to_end(&mut data).unwrap();
+
+        let path = dir.path().join("$MFT");
+        fs::write(&path, data).unwrap();
+
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_entries_match_volume() {
+        let dir = TempDir::new("libfsntfs").unwrap();
+        let mft = MftMetadataFile::open(extract_mft(&dir)).unwrap();
+        let volume = sample_volume().unwrap();
+
+        assert_eq!(
+            mft.get_number_of_file_entries().unwrap(),
+            volume.get_number_of_file_entries().unwrap()
+        );
+        assert_eq!(mft.get_volume_name().unwrap(), volume.get_name().unwrap());
+        assert_eq!(
+            mft.get_volume_version().unwrap(),
+            volume.get_version().unwrap()
+        );
+
+        let from_mft = mft
+            .get_file_entry_by_mft_idx(5)
+            .unwrap()
+            .metadata()
+            .unwrap();
+        let from_volume = volume
+            .get_file_entry_by_mft_idx(5)
+            .unwrap()
+            .metadata()
+            .unwrap();
+
+        assert_eq!(from_mft.name, from_volume.name);
+        assert_eq!(from_mft.file_reference, from_volume.file_reference);
+        assert_eq!(from_mft.creation_time, from_volume.creation_time);
+
+        let names: Vec<String> = mft
+            .iter_entries()
+            .unwrap()
+            .take(4)
+            .map(|entry| entry.unwrap().get_name().unwrap())
+            .collect();
+
+        assert_eq!(names, vec!["$MFT", "$MFTMirr", "$LogFile", "$Volume"]);
+    }
+
+    #[test]
+    fn test_content_is_not_available() {
+        let dir = TempDir::new("libfsntfs").unwrap();
+        let handle = Handle::open_file(extract_mft(&dir), LibbfioAccessFlags::Read).unwrap();
+        let mft = MftMetadataFile::open_file_object(handle).unwrap();
+
+        let mut entry = mft.get_file_entry_by_mft_idx(0).unwrap();
+        let mut buf = [0; 16];
+
+        assert!(entry.get_size().unwrap() > 0);
+        assert!(entry.read(&mut buf).is_err());
+
+        match entry.read_at(&mut buf, 0) {
+            Err(Error::ContentNotAvailable) => {}
+            other => panic!("expected ContentNotAvailable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_alternate_data_streams_are_not_available() {
+        let dir = TempDir::new("libfsntfs").unwrap();
+        let handle = Handle::open_file(extract_mft(&dir), LibbfioAccessFlags::Read).unwrap();
+        let mft = MftMetadataFile::open_file_object(handle).unwrap();
+
+        // `$Secure`, whose security descriptors are in its `$SDS` stream.
+        let entry = mft.get_file_entry_by_mft_idx(9).unwrap();
+
+        assert!(entry.get_number_of_alternate_data_streams().unwrap() > 0);
+        assert!(entry.has_alternate_data_stream_by_name("$SDS").unwrap());
+
+        match entry.get_alternate_data_stream(0).map(|_| ()) {
+            Err(Error::ContentNotAvailable) => {}
+            other => panic!("expected ContentNotAvailable, got {:?}", other),
+        }
+
+        match entry.get_alternate_data_stream_by_name("$SDS").map(|_| ()) {
+            Err(Error::ContentNotAvailable) => {}
+            other => panic!("expected ContentNotAvailable, got {:?}", other),
+        }
+    }
+}
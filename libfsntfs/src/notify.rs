@@ -0,0 +1,208 @@
+//! Controls the verbose diagnostics libfsntfs prints while parsing.
+//!
+//! Like the codepage, these are process-wide settings of libfsntfs.
+//! libfsntfs only prints anything when it is built with the opt-in `verbose_output` feature.
+use crate::error::Error;
+use crate::ffi_error::LibfsntfsErrorRefMut;
+use libfsntfs_sys::FILE;
+use log::Level;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// The `log` target of the records forwarded by `forward_to_log`.
+pub const LOG_TARGET: &str = "libfsntfs::native";
+
+extern "C" {
+    pub fn libfsntfs_notify_set_verbose(verbose: c_int);
+    pub fn libfsntfs_notify_set_stream(
+        stream: *mut FILE,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_notify_stream_open(
+        filename: *const c_char,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_notify_stream_close(error: *mut LibfsntfsErrorRefMut) -> c_int;
+}
+
+/// Enables (or disables) verbose output.
+/// Nothing is printed until a stream is set with `open_stream` or `forward_to_log`.
+pub fn set_verbose(verbose: bool) {
+    unsafe { libfsntfs_notify_set_verbose(verbose as c_int) }
+}
+
+/// Writes the verbose output to the file at `filename`.
+pub fn open_stream(filename: impl AsRef<str>) -> Result<(), Error> {
+    let c_string = CString::new(filename.as_ref()).map_err(Error::StringContainsNul)?;
+    let mut error = ptr::null_mut();
+
+    if unsafe { libfsntfs_notify_stream_open(c_string.as_ptr(), &mut error) } != 1 {
+        Err(Error::try_from(error)?)
+    } else {
+        Ok(())
+    }
+}
+
+/// Closes the stream opened with `open_stream`.
+pub fn close_stream() -> Result<(), Error> {
+    let mut error = ptr::null_mut();
+
+    if unsafe { libfsntfs_notify_stream_close(&mut error) } != 1 {
+        Err(Error::try_from(error)?)
+    } else {
+        Ok(())
+    }
+}
+
+/// Enables verbose output and forwards it to the `log` crate, under the `LOG_TARGET` target.
+/// Hex dumps of the parsed data are logged at trace level, everything else at debug level.
+/// Nothing is logged unless this crate is built with the `verbose_output` feature, which builds
+/// libfsntfs with its verbose and debug output, at a cost for every volume that is parsed.
+///
+/// The output is written to a pipe, which is read by a background thread
+/// for the rest of the process. Calling this again has no effect,
+/// unless the stream was replaced with `open_stream` in the meantime.
+#[cfg(unix)]
+pub fn forward_to_log() -> Result<(), Error> {
+    log_stream::forward_to_log()?;
+    set_verbose(true);
+
+    Ok(())
+}
+
+/// libcnotify prints data as lines of `offset: hex bytes  characters`.
+fn level_of(line: &str) -> Level {
+    let is_hex_dump = line.len() > 9
+        && line.as_bytes()[8] == b':'
+        && line[..8].chars().all(|c| c.is_ascii_hexdigit());
+
+    if is_hex_dump {
+        Level::Trace
+    } else {
+        Level::Debug
+    }
+}
+
+#[cfg(unix)]
+mod log_stream {
+    use super::{level_of, libfsntfs_notify_set_stream, LOG_TARGET};
+    use crate::error::Error;
+    use lazy_static::lazy_static;
+    use libfsntfs_sys::FILE;
+    use log::log;
+    use std::convert::TryFrom;
+    use std::fs::File;
+    use std::io::{self, BufRead, BufReader};
+    use std::os::raw::{c_char, c_int};
+    use std::os::unix::io::FromRawFd;
+    use std::ptr;
+    use std::sync::Mutex;
+    use std::thread;
+
+    /// Line buffering, which has the same value on all unix platforms.
+    const IOLBF: c_int = 1;
+
+    extern "C" {
+        fn pipe(fds: *mut c_int) -> c_int;
+        fn fdopen(fd: c_int, mode: *const c_char) -> *mut FILE;
+        fn setvbuf(stream: *mut FILE, buffer: *mut c_char, mode: c_int, size: usize) -> c_int;
+    }
+
+    /// The write end of the pipe, which is never closed since libfsntfs may still write to it.
+    struct Stream(*mut FILE);
+
+    // The stream is only handed over to libfsntfs.
+    unsafe impl Send for Stream {}
+
+    lazy_static! {
+        static ref STREAM: Mutex<Option<Stream>> = Mutex::new(None);
+    }
+
+    pub(super) fn forward_to_log() -> Result<(), Error> {
+        let mut stream = STREAM
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let file = match &*stream {
+            Some(Stream(file)) => *file,
+            None => {
+                let file = open_pipe().map_err(Error::Io)?;
+                *stream = Some(Stream(file));
+                file
+            }
+        };
+
+        let mut error = ptr::null_mut();
+
+        if unsafe { libfsntfs_notify_set_stream(file, &mut error) } != 1 {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn open_pipe() -> io::Result<*mut FILE> {
+        let mut fds = [0 as c_int; 2];
+
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let reader = unsafe { File::from_raw_fd(fds[0]) };
+        let file = unsafe { fdopen(fds[1], b"w\0".as_ptr() as *const c_char) };
+
+        if file.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Otherwise messages are only forwarded once a few KiBs are buffered.
+        unsafe { setvbuf(file, ptr::null_mut(), IOLBF, 0) };
+
+        thread::Builder::new()
+            .name("libfsntfs-notify".to_owned())
+            .spawn(move || forward_lines(reader))?;
+
+        Ok(file)
+    }
+
+    fn forward_lines(reader: File) {
+        let mut reader = BufReader::new(reader);
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&line);
+                    let line = line.trim_end();
+
+                    if !line.is_empty() {
+                        log!(target: LOG_TARGET, level_of(line), "{}", line);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_of() {
+        assert_eq!(
+            level_of("00000010: 46 49 4c 45 30 00 03 00  FILE0..."),
+            Level::Trace
+        );
+        assert_eq!(
+            level_of("libfsntfs_mft_entry_read_header: signature: FILE"),
+            Level::Debug
+        );
+        assert_eq!(level_of(""), Level::Debug);
+    }
+}
@@ -0,0 +1,554 @@
+//! Parses MBR (including extended partitions) and GPT partition tables,
+//! so volumes inside full disk images can be opened with `Volume::open_partition`.
+//!
+//! MBR partition tables are assumed to use 512-byte sectors. GPT partition tables use the sector
+//! size of the disk, which is detected from the location of the GPT header (512 or 4096 bytes).
+use crate::error::Error;
+use crate::utils::{u32_le, u64_le};
+use std::fmt::{self, Display, Formatter};
+use std::io::{Read, Seek, SeekFrom};
+
+pub const SECTOR_SIZE: u64 = 512;
+/// The sector size of "4K native" disks.
+pub const SECTOR_SIZE_4KN: u64 = 4096;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const MBR_PARTITION_ENTRIES_OFFSET: usize = 446;
+const MBR_PARTITION_ENTRY_SIZE: usize = 16;
+
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+const MBR_TYPE_NTFS: u8 = 0x07;
+
+/// Extended partitions are a linked list, which can contain loops in corrupt images.
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+/// There are usually 128 entries, but the header can claim much more.
+const MAX_GPT_ENTRIES: u32 = 1024;
+/// Entry sizes are multiples of 128 bytes, which are usually 128 bytes.
+const GPT_MIN_ENTRY_SIZE: u32 = 128;
+const GPT_MAX_ENTRY_SIZE: u32 = 4096;
+/// The table is usually 16 KiB, this is only a guard against huge allocations.
+const GPT_MAX_TABLE_SIZE: u32 = 1024 * 1024;
+
+/// "Basic data partition", used by Windows for NTFS (as well as FAT and exFAT).
+const GPT_TYPE_BASIC_DATA: Guid = Guid([
+    0xa2, 0xa0, 0xd0, 0xeb, 0xe5, 0xb9, 0x33, 0x44, 0x87, 0xc0, 0x68, 0xb6, 0xb7, 0x26, 0x99, 0xc7,
+]);
+
+/// A GUID, in its on-disk (mixed-endian) representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub fn is_nil(&self) -> bool {
+        self.0.iter().all(|b| *b == 0)
+    }
+}
+
+impl Display for Guid {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let b = &self.0;
+
+        write!(
+            f,
+            "{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-",
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9]
+        )?;
+
+        for byte in &b[10..] {
+            write!(f, "{:02X}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PartitionTableType {
+    Mbr,
+    Gpt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartitionType {
+    /// The partition type byte of an MBR partition entry.
+    Mbr(u8),
+    /// The partition type GUID of a GPT partition entry.
+    Gpt(Guid),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partition {
+    /// The index of the partition, as used by `Volume::open_partition`.
+    pub index: usize,
+    pub table_type: PartitionTableType,
+    pub partition_type: PartitionType,
+    /// The offset of the partition from the start of the disk, in bytes.
+    pub offset: u64,
+    /// The size of the partition, in bytes.
+    pub size: u64,
+    /// True for logical partitions inside an MBR extended partition.
+    pub is_logical: bool,
+    /// The name of a GPT partition (MBR partitions have no names).
+    pub name: Option<String>,
+}
+
+impl Partition {
+    /// Returns true if the partition type is one used for NTFS.
+    /// The same types are used by other file systems, so this is only a hint.
+    pub fn may_be_ntfs(&self) -> bool {
+        match &self.partition_type {
+            PartitionType::Mbr(partition_type) => *partition_type == MBR_TYPE_NTFS,
+            PartitionType::Gpt(partition_type) => *partition_type == GPT_TYPE_BASIC_DATA,
+        }
+    }
+}
+
+fn read_sector<R: Read + Seek>(
+    reader: &mut R,
+    lba: u64,
+    sector_size: u64,
+) -> Result<Vec<u8>, Error> {
+    let mut sector = vec![0; sector_size as usize];
+
+    reader
+        .seek(SeekFrom::Start(sector_offset(lba, sector_size)?))
+        .and_then(|_| reader.read_exact(&mut sector))
+        .map_err(Error::Io)?;
+
+    Ok(sector)
+}
+
+struct MbrEntry {
+    partition_type: u8,
+    start_lba: u64,
+    number_of_sectors: u64,
+}
+
+fn mbr_entries(sector: &[u8]) -> Vec<MbrEntry> {
+    (0..4)
+        .map(|i| {
+            let entry_offset = MBR_PARTITION_ENTRIES_OFFSET + i * MBR_PARTITION_ENTRY_SIZE;
+            let entry = &sector[entry_offset..entry_offset + MBR_PARTITION_ENTRY_SIZE];
+
+            MbrEntry {
+                partition_type: entry[4],
+                start_lba: u64::from(u32_le(entry, 8)),
+                number_of_sectors: u64::from(u32_le(entry, 12)),
+            }
+        })
+        .collect()
+}
+
+/// Reads the partition table of a disk image.
+/// Images without an MBR signature are considered to have no partitions.
+pub fn read_partitions<R: Read + Seek>(reader: &mut R) -> Result<Vec<Partition>, Error> {
+    let mbr = read_sector(reader, 0, SECTOR_SIZE)?;
+
+    if mbr[510..512] != MBR_SIGNATURE {
+        return Ok(vec![]);
+    }
+
+    let entries = mbr_entries(&mbr);
+
+    if entries
+        .iter()
+        .any(|e| e.partition_type == MBR_TYPE_GPT_PROTECTIVE)
+    {
+        return read_gpt_partitions(reader);
+    }
+
+    let mut partitions = vec![];
+    let mut extended_partitions = vec![];
+
+    for entry in entries {
+        if entry.partition_type == 0 || entry.number_of_sectors == 0 {
+            continue;
+        }
+
+        if MBR_TYPES_EXTENDED.contains(&entry.partition_type) {
+            extended_partitions.push(entry.start_lba);
+            continue;
+        }
+
+        partitions.push(Partition {
+            index: partitions.len(),
+            table_type: PartitionTableType::Mbr,
+            partition_type: PartitionType::Mbr(entry.partition_type),
+            offset: entry.start_lba * SECTOR_SIZE,
+            size: entry.number_of_sectors * SECTOR_SIZE,
+            is_logical: false,
+            name: None,
+        });
+    }
+
+    for extended_start_lba in extended_partitions {
+        read_logical_partitions(reader, extended_start_lba, &mut partitions)?;
+    }
+
+    Ok(partitions)
+}
+
+/// Walks the chain of extended boot records of an extended partition.
+fn read_logical_partitions<R: Read + Seek>(
+    reader: &mut R,
+    extended_start_lba: u64,
+    partitions: &mut Vec<Partition>,
+) -> Result<(), Error> {
+    let mut ebr_lba = extended_start_lba;
+
+    for _ in 0..MAX_LOGICAL_PARTITIONS {
+        let ebr = read_sector(reader, ebr_lba, SECTOR_SIZE)?;
+
+        if ebr[510..512] != MBR_SIGNATURE {
+            return Err(Error::InvalidPartitionTable(format!(
+                "missing extended boot record signature at sector {}",
+                ebr_lba
+            )));
+        }
+
+        let entries = mbr_entries(&ebr);
+
+        // The first entry is the logical partition (relative to this EBR),
+        // the second one points to the next EBR (relative to the extended partition).
+        let logical = &entries[0];
+        if logical.partition_type != 0 && logical.number_of_sectors != 0 {
+            partitions.push(Partition {
+                index: partitions.len(),
+                table_type: PartitionTableType::Mbr,
+                partition_type: PartitionType::Mbr(logical.partition_type),
+                offset: (ebr_lba + logical.start_lba) * SECTOR_SIZE,
+                size: logical.number_of_sectors * SECTOR_SIZE,
+                is_logical: true,
+                name: None,
+            });
+        }
+
+        let next = &entries[1];
+        if next.partition_type == 0 || next.start_lba == 0 {
+            return Ok(());
+        }
+
+        ebr_lba = extended_start_lba + next.start_lba;
+    }
+
+    Err(Error::InvalidPartitionTable(format!(
+        "more than {} logical partitions",
+        MAX_LOGICAL_PARTITIONS
+    )))
+}
+
+/// Reads the GPT header, which is in the second sector of the disk,
+/// returning it along with the sector size of the disk.
+fn read_gpt_header<R: Read + Seek>(reader: &mut R) -> Result<(Vec<u8>, u64), Error> {
+    for &sector_size in &[SECTOR_SIZE, SECTOR_SIZE_4KN] {
+        // A disk with 512-byte sectors may be too small to have a second 4 KiB sector.
+        if let Ok(header) = read_sector(reader, 1, sector_size) {
+            if &header[0..8] == GPT_SIGNATURE {
+                return Ok((header, sector_size));
+            }
+        }
+    }
+
+    Err(Error::InvalidPartitionTable(
+        "missing GPT header signature".to_owned(),
+    ))
+}
+
+fn read_gpt_partitions<R: Read + Seek>(reader: &mut R) -> Result<Vec<Partition>, Error> {
+    let (header, sector_size) = read_gpt_header(reader)?;
+
+    let entries_lba = u64_le(&header, 72);
+    let number_of_entries = u32_le(&header, 80);
+    let entry_size = u32_le(&header, 84);
+
+    let is_supported = number_of_entries <= MAX_GPT_ENTRIES
+        && (GPT_MIN_ENTRY_SIZE..=GPT_MAX_ENTRY_SIZE).contains(&entry_size)
+        && entry_size % GPT_MIN_ENTRY_SIZE == 0
+        && number_of_entries * entry_size <= GPT_MAX_TABLE_SIZE;
+
+    if !is_supported {
+        return Err(Error::InvalidPartitionTable(format!(
+            "unsupported GPT layout ({} entries of {} bytes)",
+            number_of_entries, entry_size
+        )));
+    }
+
+    let mut entries = vec![0; number_of_entries as usize * entry_size as usize];
+
+    reader
+        .seek(SeekFrom::Start(sector_offset(entries_lba, sector_size)?))
+        .and_then(|_| reader.read_exact(&mut entries))
+        .map_err(Error::Io)?;
+
+    let mut partitions = vec![];
+
+    for entry in entries.chunks(entry_size as usize) {
+        let mut partition_type = Guid([0; 16]);
+        partition_type.0.copy_from_slice(&entry[0..16]);
+
+        if partition_type.is_nil() {
+            continue;
+        }
+
+        let first_lba = u64_le(entry, 32);
+        let last_lba = u64_le(entry, 40);
+
+        if last_lba < first_lba {
+            return Err(Error::InvalidPartitionTable(format!(
+                "GPT partition ends (sector {}) before it starts (sector {})",
+                last_lba, first_lba
+            )));
+        }
+
+        let name: Vec<u16> = entry[56..128]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0)
+            .collect();
+
+        partitions.push(Partition {
+            index: partitions.len(),
+            table_type: PartitionTableType::Gpt,
+            partition_type: PartitionType::Gpt(partition_type),
+            offset: sector_offset(first_lba, sector_size)?,
+            size: sector_offset(last_lba - first_lba, sector_size)?
+                .checked_add(sector_size)
+                .ok_or_else(|| sector_out_of_range(last_lba))?,
+            is_logical: false,
+            name: Some(String::from_utf16_lossy(&name)),
+        });
+    }
+
+    Ok(partitions)
+}
+
+/// The byte offset of a sector, which is read from the (possibly hostile) image.
+fn sector_offset(lba: u64, sector_size: u64) -> Result<u64, Error> {
+    lba.checked_mul(sector_size)
+        .ok_or_else(|| sector_out_of_range(lba))
+}
+
+fn sector_out_of_range(lba: u64) -> Error {
+    Error::InvalidPartitionTable(format!("sector {} is out of range", lba))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    pub(crate) fn set_mbr_entry(
+        disk: &mut [u8],
+        lba: u64,
+        i: usize,
+        partition_type: u8,
+        start: u32,
+        sectors: u32,
+    ) {
+        let sector = &mut disk[(lba * SECTOR_SIZE) as usize..((lba + 1) * SECTOR_SIZE) as usize];
+        let offset = MBR_PARTITION_ENTRIES_OFFSET + i * MBR_PARTITION_ENTRY_SIZE;
+
+        sector[offset + 4] = partition_type;
+        sector[offset + 8..offset + 12].copy_from_slice(&start.to_le_bytes());
+        sector[offset + 12..offset + 16].copy_from_slice(&sectors.to_le_bytes());
+        sector[510..512].copy_from_slice(&MBR_SIGNATURE);
+    }
+
+    fn disk(sectors: u64) -> Vec<u8> {
+        vec![0; (sectors * SECTOR_SIZE) as usize]
+    }
+
+    #[test]
+    fn test_no_partition_table() {
+        assert!(read_partitions(&mut Cursor::new(disk(2)))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_mbr_with_extended_partitions() {
+        let mut disk = disk(64);
+
+        set_mbr_entry(&mut disk, 0, 0, MBR_TYPE_NTFS, 2, 8);
+        set_mbr_entry(&mut disk, 0, 1, 0x0f, 16, 40);
+
+        // The first EBR has a logical partition at sector 18 and links to the next EBR at 32.
+        set_mbr_entry(&mut disk, 16, 0, 0x0b, 2, 4);
+        set_mbr_entry(&mut disk, 16, 1, 0x05, 16, 10);
+
+        // The second EBR has a logical partition at sector 35.
+        set_mbr_entry(&mut disk, 32, 0, MBR_TYPE_NTFS, 3, 6);
+
+        let partitions = read_partitions(&mut Cursor::new(disk)).unwrap();
+
+        let layout: Vec<_> = partitions
+            .iter()
+            .map(|p| {
+                (
+                    p.index,
+                    p.offset / SECTOR_SIZE,
+                    p.size / SECTOR_SIZE,
+                    p.is_logical,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            layout,
+            vec![(0, 2, 8, false), (1, 18, 4, true), (2, 35, 6, true)]
+        );
+        assert!(partitions[0].may_be_ntfs());
+        assert!(!partitions[1].may_be_ntfs());
+    }
+
+    #[test]
+    fn test_extended_partition_loop_is_an_error() {
+        let mut disk = disk(32);
+
+        set_mbr_entry(&mut disk, 0, 0, 0x05, 8, 16);
+        set_mbr_entry(&mut disk, 8, 0, MBR_TYPE_NTFS, 1, 1);
+        set_mbr_entry(&mut disk, 8, 1, 0x05, 8, 8);
+
+        // The second EBR links back to itself.
+        set_mbr_entry(&mut disk, 16, 0, MBR_TYPE_NTFS, 1, 1);
+        set_mbr_entry(&mut disk, 16, 1, 0x05, 8, 8);
+
+        assert!(read_partitions(&mut Cursor::new(disk)).is_err());
+    }
+
+    /// A disk with a GPT of 4 entries at sector 2, of which the second one is unused.
+    fn gpt_disk(entries: &[(usize, Guid, u64, u64, &str)]) -> Vec<u8> {
+        gpt_disk_with_sector_size(SECTOR_SIZE, entries)
+    }
+
+    fn gpt_disk_with_sector_size(
+        sector_size: u64,
+        entries: &[(usize, Guid, u64, u64, &str)],
+    ) -> Vec<u8> {
+        let sector_size = sector_size as usize;
+        let mut disk = vec![0; 40 * sector_size];
+
+        set_mbr_entry(&mut disk, 0, 0, MBR_TYPE_GPT_PROTECTIVE, 1, 39);
+
+        let header = &mut disk[sector_size..2 * sector_size];
+        header[0..8].copy_from_slice(GPT_SIGNATURE);
+        header[72..80].copy_from_slice(&2_u64.to_le_bytes());
+        header[80..84].copy_from_slice(&4_u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128_u32.to_le_bytes());
+
+        for (i, partition_type, first_lba, last_lba, name) in entries {
+            let offset = 2 * sector_size + i * 128;
+            let entry = &mut disk[offset..offset + 128];
+
+            entry[0..16].copy_from_slice(&partition_type.0);
+            entry[32..40].copy_from_slice(&first_lba.to_le_bytes());
+            entry[40..48].copy_from_slice(&last_lba.to_le_bytes());
+
+            for (j, c) in name.encode_utf16().enumerate() {
+                entry[56 + j * 2..58 + j * 2].copy_from_slice(&c.to_le_bytes());
+            }
+        }
+
+        disk
+    }
+
+    fn set_gpt_layout(disk: &mut [u8], entries_lba: u64, number_of_entries: u32, entry_size: u32) {
+        let header = &mut disk[SECTOR_SIZE as usize..2 * SECTOR_SIZE as usize];
+        header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&number_of_entries.to_le_bytes());
+        header[84..88].copy_from_slice(&entry_size.to_le_bytes());
+    }
+
+    #[test]
+    fn test_gpt() {
+        let disk = gpt_disk(&[
+            (0, GPT_TYPE_BASIC_DATA, 34, 35, "Data"),
+            (2, Guid([1; 16]), 36, 39, "Other"),
+        ]);
+
+        let partitions = read_partitions(&mut Cursor::new(disk)).unwrap();
+
+        assert_eq!(partitions.len(), 2);
+        assert_eq!(partitions[0].table_type, PartitionTableType::Gpt);
+        assert_eq!(partitions[0].offset, 34 * SECTOR_SIZE);
+        assert_eq!(partitions[0].size, 2 * SECTOR_SIZE);
+        assert_eq!(partitions[0].name, Some("Data".to_string()));
+        assert!(partitions[0].may_be_ntfs());
+        assert_eq!(partitions[1].index, 1);
+        assert_eq!(partitions[1].name, Some("Other".to_string()));
+        assert!(!partitions[1].may_be_ntfs());
+    }
+
+    #[test]
+    fn test_gpt_of_4kn_disk() {
+        let gpt =
+            gpt_disk_with_sector_size(SECTOR_SIZE_4KN, &[(0, GPT_TYPE_BASIC_DATA, 6, 7, "Data")]);
+        let partitions = read_partitions(&mut Cursor::new(gpt)).unwrap();
+
+        assert_eq!(partitions.len(), 1);
+        assert_eq!(partitions[0].offset, 6 * SECTOR_SIZE_4KN);
+        assert_eq!(partitions[0].size, 2 * SECTOR_SIZE_4KN);
+
+        // Without a GPT header at either sector size.
+        let mut disk = disk(4);
+        set_mbr_entry(&mut disk, 0, 0, MBR_TYPE_GPT_PROTECTIVE, 1, 3);
+
+        match read_partitions(&mut Cursor::new(disk)) {
+            Err(Error::InvalidPartitionTable(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_gpt_with_huge_entries_is_an_error() {
+        let mut disk = gpt_disk(&[(0, GPT_TYPE_BASIC_DATA, 34, 35, "Data")]);
+
+        set_gpt_layout(&mut disk, 2, MAX_GPT_ENTRIES, 0xffff_ffff);
+        assert!(read_partitions(&mut Cursor::new(disk.clone())).is_err());
+
+        // Entries which are not a multiple of 128 bytes.
+        set_gpt_layout(&mut disk, 2, 4, 200);
+        assert!(read_partitions(&mut Cursor::new(disk.clone())).is_err());
+
+        // Entries of a valid size, but a table of 4 MiB.
+        set_gpt_layout(&mut disk, 2, MAX_GPT_ENTRIES, GPT_MAX_ENTRY_SIZE);
+        assert!(read_partitions(&mut Cursor::new(disk)).is_err());
+    }
+
+    #[test]
+    fn test_gpt_with_out_of_range_entries_lba_is_an_error() {
+        let mut disk = gpt_disk(&[(0, GPT_TYPE_BASIC_DATA, 34, 35, "Data")]);
+
+        set_gpt_layout(&mut disk, u64::MAX / 2, 4, 128);
+
+        match read_partitions(&mut Cursor::new(disk)) {
+            Err(Error::InvalidPartitionTable(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_gpt_with_out_of_range_partition_is_an_error() {
+        let disk = gpt_disk(&[(0, GPT_TYPE_BASIC_DATA, u64::MAX / 2, u64::MAX / 2, "Data")]);
+        assert!(read_partitions(&mut Cursor::new(disk)).is_err());
+
+        // Starts in range, but its size overflows.
+        let disk = gpt_disk(&[(0, GPT_TYPE_BASIC_DATA, 0, u64::MAX / SECTOR_SIZE, "Data")]);
+
+        match read_partitions(&mut Cursor::new(disk)) {
+            Err(Error::InvalidPartitionTable(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_guid_display() {
+        assert_eq!(
+            GPT_TYPE_BASIC_DATA.to_string(),
+            "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7"
+        );
+    }
+}
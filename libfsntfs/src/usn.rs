@@ -0,0 +1,611 @@
+//! Parses USN change journal records from an extracted `$UsnJrnl:$J` stream.
+//!
+//! Collection tools usually keep the sparse start of the stream (which reads as zeros),
+//! and copies of live journals can contain partially written records,
+//! so `UsnJournalFile` skips zero-filled runs and rescans past corrupt records.
+use crate::error::Error;
+use crate::file_entry::FileEntry;
+use crate::mft_metadata_file::MftMetadataFile;
+use crate::utils::{datetime_from_filetime, u16_le, u32_le, u64_le};
+use crate::volume::{mft_entry_index_from_reference, MftEntryIndex, Volume};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// The reasons for a change (see `USN_REASON_*` in the Windows SDK).
+pub mod reason {
+    pub const DATA_OVERWRITE: u32 = 0x0000_0001;
+    pub const DATA_EXTEND: u32 = 0x0000_0002;
+    pub const DATA_TRUNCATION: u32 = 0x0000_0004;
+    pub const NAMED_DATA_OVERWRITE: u32 = 0x0000_0010;
+    pub const NAMED_DATA_EXTEND: u32 = 0x0000_0020;
+    pub const NAMED_DATA_TRUNCATION: u32 = 0x0000_0040;
+    pub const FILE_CREATE: u32 = 0x0000_0100;
+    pub const FILE_DELETE: u32 = 0x0000_0200;
+    pub const EA_CHANGE: u32 = 0x0000_0400;
+    pub const SECURITY_CHANGE: u32 = 0x0000_0800;
+    pub const RENAME_OLD_NAME: u32 = 0x0000_1000;
+    pub const RENAME_NEW_NAME: u32 = 0x0000_2000;
+    pub const INDEXABLE_CHANGE: u32 = 0x0000_4000;
+    pub const BASIC_INFO_CHANGE: u32 = 0x0000_8000;
+    pub const HARD_LINK_CHANGE: u32 = 0x0001_0000;
+    pub const COMPRESSION_CHANGE: u32 = 0x0002_0000;
+    pub const ENCRYPTION_CHANGE: u32 = 0x0004_0000;
+    pub const OBJECT_ID_CHANGE: u32 = 0x0008_0000;
+    pub const REPARSE_POINT_CHANGE: u32 = 0x0010_0000;
+    pub const STREAM_CHANGE: u32 = 0x0020_0000;
+    pub const TRANSACTED_CHANGE: u32 = 0x0040_0000;
+    pub const INTEGRITY_CHANGE: u32 = 0x0080_0000;
+    pub const CLOSE: u32 = 0x8000_0000;
+}
+
+/// Records are 8-byte aligned.
+const RECORD_ALIGNMENT: u64 = 8;
+/// The size of the fixed part of a version 2 record.
+const V2_HEADER_SIZE: usize = 60;
+/// The size of the fixed part of a version 3 record (with 128-bit file references).
+const V3_HEADER_SIZE: usize = 76;
+/// File names are at most 255 UTF-16 characters, so records are much smaller than this.
+const MAX_RECORD_LENGTH: usize = 4096;
+/// The size of the reads from the stream, records (and zero-filled runs) are parsed from a buffer
+/// of this size, which is only refilled once it is exhausted.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+const ROOT_DIRECTORY_MFT_ENTRY_INDEX: MftEntryIndex = 5;
+/// Guards against loops in corrupt directory structures.
+const MAX_PATH_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsnRecord {
+    /// The offset of the record in the journal stream.
+    pub offset: u64,
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// Version 3 records have 128-bit file references, of which NTFS only uses the lower 64 bits.
+    pub file_reference: u64,
+    pub parent_file_reference: u64,
+    pub usn: u64,
+    pub timestamp: Option<DateTime<Utc>>,
+    /// A combination of the `reason` constants.
+    pub reason: u32,
+    pub source_info: u32,
+    pub security_descriptor_identifier: u32,
+    pub file_attribute_flags: u32,
+    pub name: String,
+    /// The path of the parent directory (like `\Windows\System32`),
+    /// when resolving parent paths and the parent directory still exists.
+    pub parent_path: Option<String>,
+}
+
+impl UsnRecord {
+    /// Parses a version 2 or 3 record, `data` must hold the whole record.
+    pub fn from_bytes(data: &[u8], offset: u64) -> Result<Self, Error> {
+        let invalid = |reason: &str| {
+            Error::InvalidUsnRecord(format!("record at offset {} {}", offset, reason))
+        };
+
+        if data.len() < 8 {
+            return Err(invalid("is too small"));
+        }
+
+        let record_length = u32_le(data, 0) as usize;
+        let major_version = u16_le(data, 4);
+        let minor_version = u16_le(data, 6);
+
+        let header_size = match major_version {
+            2 => V2_HEADER_SIZE,
+            3 => V3_HEADER_SIZE,
+            _ => return Err(invalid("has an unsupported version")),
+        };
+
+        if record_length < header_size || record_length > data.len() {
+            return Err(invalid("has an invalid length"));
+        }
+
+        // Version 3 records have 16 byte file references, which shift the rest of the fields.
+        let (file_reference, parent_file_reference, fields) = if major_version == 2 {
+            (u64_le(data, 8), u64_le(data, 16), 24)
+        } else {
+            (u64_le(data, 8), u64_le(data, 24), 40)
+        };
+
+        let name_length = u16_le(data, fields + 32) as usize;
+        let name_offset = u16_le(data, fields + 34) as usize;
+
+        if name_offset < header_size || name_offset + name_length > record_length {
+            return Err(invalid("has a name outside of the record"));
+        }
+
+        if name_length % 2 != 0 {
+            return Err(invalid("has a name of an odd length"));
+        }
+
+        let name: Vec<u16> = data[name_offset..name_offset + name_length]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+
+        let timestamp = u64_le(data, fields + 8);
+
+        Ok(UsnRecord {
+            offset,
+            major_version,
+            minor_version,
+            file_reference,
+            parent_file_reference,
+            usn: u64_le(data, fields),
+            timestamp: if timestamp > 0 {
+                Some(datetime_from_filetime(timestamp))
+            } else {
+                None
+            },
+            reason: u32_le(data, fields + 16),
+            source_info: u32_le(data, fields + 20),
+            security_descriptor_identifier: u32_le(data, fields + 24),
+            file_attribute_flags: u32_le(data, fields + 28),
+            name: String::from_utf16_lossy(&name),
+            parent_path: None,
+        })
+    }
+
+    /// Returns true if all the `reason` flags are set.
+    pub fn has_reason(&self, reason: u32) -> bool {
+        self.reason & reason == reason
+    }
+
+    /// The MFT entry index of the file (the file reference without the sequence number).
+    pub fn mft_entry_index(&self) -> MftEntryIndex {
+        mft_entry_index_from_reference(self.file_reference)
+    }
+
+    /// The full path of the file, when the parent path was resolved.
+    pub fn path(&self) -> Option<String> {
+        self.parent_path.as_ref().map(|parent| {
+            if parent.ends_with('\\') {
+                format!("{}{}", parent, self.name)
+            } else {
+                format!("{}\\{}", parent, self.name)
+            }
+        })
+    }
+}
+
+/// A source of file entries to resolve parent paths with.
+pub trait FileEntrySource {
+    fn get_file_entry(&self, idx: MftEntryIndex) -> Result<FileEntry, Error>;
+}
+
+impl FileEntrySource for Volume {
+    fn get_file_entry(&self, idx: MftEntryIndex) -> Result<FileEntry, Error> {
+        self.get_file_entry_by_mft_idx(idx)
+    }
+}
+
+impl FileEntrySource for MftMetadataFile {
+    fn get_file_entry(&self, idx: MftEntryIndex) -> Result<FileEntry, Error> {
+        self.get_file_entry_by_mft_idx(idx)
+    }
+}
+
+/// Resolves directory file references to paths, caching the results.
+struct PathResolver<'a> {
+    source: &'a dyn FileEntrySource,
+    paths: HashMap<u64, Option<String>>,
+}
+
+impl<'a> PathResolver<'a> {
+    fn resolve(&mut self, directory_reference: u64) -> Option<String> {
+        if let Some(path) = self.paths.get(&directory_reference) {
+            return path.clone();
+        }
+
+        let path = self.resolve_uncached(directory_reference);
+        self.paths.insert(directory_reference, path.clone());
+
+        path
+    }
+
+    /// Returns None if a directory on the way to the root was deleted (or its entry reused).
+    fn resolve_uncached(&self, directory_reference: u64) -> Option<String> {
+        let mut names = vec![];
+        let mut reference = directory_reference;
+
+        for _ in 0..MAX_PATH_DEPTH {
+            let idx = mft_entry_index_from_reference(reference);
+
+            if idx == ROOT_DIRECTORY_MFT_ENTRY_INDEX {
+                names.reverse();
+                return Some(format!("\\{}", names.join("\\")));
+            }
+
+            let entry = self.source.get_file_entry(idx).ok()?;
+
+            if entry.get_file_reference().ok()? != reference {
+                return None;
+            }
+
+            names.push(entry.get_name().ok()?);
+            reference = entry.get_parent_file_reference().ok()?;
+        }
+
+        None
+    }
+}
+
+/// Iterates over the records of a `$UsnJrnl:$J` stream.
+/// The source can be any seekable reader, like a `File` or a libbfio `Handle`.
+pub struct UsnJournalFile<'a, R> {
+    reader: R,
+    size: u64,
+    offset: u64,
+    /// The bytes of the stream at `buffer_offset`.
+    buffer: Vec<u8>,
+    buffer_offset: u64,
+    resolver: Option<PathResolver<'a>>,
+    skipped_zero_bytes: u64,
+    skipped_corrupt_bytes: u64,
+    failed: bool,
+}
+
+impl<'a, R: Read + Seek> UsnJournalFile<'a, R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let size = reader.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+
+        Ok(UsnJournalFile {
+            reader,
+            size,
+            offset: 0,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            buffer_offset: 0,
+            resolver: None,
+            skipped_zero_bytes: 0,
+            skipped_corrupt_bytes: 0,
+            failed: false,
+        })
+    }
+
+    /// Resolve the parent path of records (see `UsnRecord::parent_path`)
+    /// using the file entries of a `Volume` or an `MftMetadataFile`.
+    pub fn resolve_parent_paths(mut self, source: &'a dyn FileEntrySource) -> Self {
+        self.resolver = Some(PathResolver {
+            source,
+            paths: HashMap::new(),
+        });
+        self
+    }
+
+    /// The number of zero bytes (usually the sparse start of the stream) skipped so far.
+    pub fn skipped_zero_bytes(&self) -> u64 {
+        self.skipped_zero_bytes
+    }
+
+    /// The number of bytes skipped so far while rescanning past corrupt records.
+    pub fn skipped_corrupt_bytes(&self) -> u64 {
+        self.skipped_corrupt_bytes
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// The buffered bytes from the current offset on, at least `len` of them
+    /// (unless the stream ends before). The buffer is only refilled when it holds fewer.
+    fn buffered(&mut self, len: usize) -> io::Result<&[u8]> {
+        let buffer_end = self.buffer_offset + self.buffer.len() as u64;
+        let is_buffered = self.offset >= self.buffer_offset
+            && self.offset <= buffer_end
+            && (self.offset + len as u64 <= buffer_end || buffer_end == self.size);
+
+        if !is_buffered {
+            let len = (self.size - self.offset).min(BUFFER_SIZE as u64) as usize;
+
+            self.buffer.resize(len, 0);
+            self.reader.seek(SeekFrom::Start(self.offset))?;
+            self.reader.read_exact(&mut self.buffer)?;
+            self.buffer_offset = self.offset;
+        }
+
+        Ok(&self.buffer[(self.offset - self.buffer_offset) as usize..])
+    }
+
+    /// Moves to the first non-zero aligned position, starting at the current offset.
+    fn skip_zeros(&mut self) -> io::Result<()> {
+        while self.offset < self.size {
+            let buffered = self.buffered(RECORD_ALIGNMENT as usize)?;
+
+            match buffered.iter().position(|b| *b != 0) {
+                Some(position) => {
+                    let skipped = position as u64 / RECORD_ALIGNMENT * RECORD_ALIGNMENT;
+                    self.offset += skipped;
+                    self.skipped_zero_bytes += skipped;
+                    return Ok(());
+                }
+                None => {
+                    let skipped = buffered.len() as u64;
+                    self.offset += skipped;
+                    self.skipped_zero_bytes += skipped;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<UsnRecord>> {
+        loop {
+            self.skip_zeros()?;
+
+            if self.offset >= self.size {
+                return Ok(None);
+            }
+
+            let offset = self.offset;
+            let buffered = self.buffered(4)?;
+            let record_length = if buffered.len() >= 4 {
+                u32_le(buffered, 0) as usize
+            } else {
+                0
+            };
+
+            let is_aligned = record_length as u64 % RECORD_ALIGNMENT == 0;
+
+            if is_aligned && record_length >= 8 && record_length <= MAX_RECORD_LENGTH {
+                let buffered = self.buffered(record_length)?;
+                let data = &buffered[..record_length.min(buffered.len())];
+
+                // Other versions (like the range tracking records of version 4) are skipped whole.
+                if data.len() == record_length && u16_le(data, 4) > 3 {
+                    self.offset += record_length as u64;
+                    continue;
+                }
+
+                if let Ok(record) = UsnRecord::from_bytes(data, offset) {
+                    self.offset += record_length as u64;
+                    return Ok(Some(record));
+                }
+            }
+
+            // Rescans from the next aligned position, which is usually still buffered.
+            self.offset += RECORD_ALIGNMENT;
+            self.skipped_corrupt_bytes += RECORD_ALIGNMENT;
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Iterator for UsnJournalFile<'a, R> {
+    type Item = Result<UsnRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        match self.next_record() {
+            Ok(Some(mut record)) => {
+                if let Some(resolver) = &mut self.resolver {
+                    record.parent_path = resolver.resolve(record.parent_file_reference);
+                }
+
+                Some(Ok(record))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                // IO errors are not recoverable by rescanning.
+                self.failed = true;
+                Some(Err(Error::Io(e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+    use std::io::Cursor;
+
+    fn v2_record(usn: u64, file_reference: u64, parent: u64, reason: u32, name: &str) -> Vec<u8> {
+        let name: Vec<u8> = name
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes().to_vec())
+            .collect();
+        let length = (V2_HEADER_SIZE + name.len() + 7) / 8 * 8;
+
+        let mut record = vec![0; length];
+        record[0..4].copy_from_slice(&(length as u32).to_le_bytes());
+        record[4..6].copy_from_slice(&2_u16.to_le_bytes());
+        record[8..16].copy_from_slice(&file_reference.to_le_bytes());
+        record[16..24].copy_from_slice(&parent.to_le_bytes());
+        record[24..32].copy_from_slice(&usn.to_le_bytes());
+        record[32..40].copy_from_slice(&131_000_000_000_000_000_u64.to_le_bytes());
+        record[40..44].copy_from_slice(&reason.to_le_bytes());
+        record[56..58].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        record[58..60].copy_from_slice(&(V2_HEADER_SIZE as u16).to_le_bytes());
+        record[V2_HEADER_SIZE..V2_HEADER_SIZE + name.len()].copy_from_slice(&name);
+
+        record
+    }
+
+    fn v3_record(usn: u64, file_reference: u64, name: &str) -> Vec<u8> {
+        let name: Vec<u8> = name
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes().to_vec())
+            .collect();
+        let length = (V3_HEADER_SIZE + name.len() + 7) / 8 * 8;
+
+        let mut record = vec![0; length];
+        record[0..4].copy_from_slice(&(length as u32).to_le_bytes());
+        record[4..6].copy_from_slice(&3_u16.to_le_bytes());
+        record[8..16].copy_from_slice(&file_reference.to_le_bytes());
+        record[40..48].copy_from_slice(&usn.to_le_bytes());
+        record[56..60].copy_from_slice(&reason::CLOSE.to_le_bytes());
+        record[72..74].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        record[74..76].copy_from_slice(&(V3_HEADER_SIZE as u16).to_le_bytes());
+        record[V3_HEADER_SIZE..V3_HEADER_SIZE + name.len()].copy_from_slice(&name);
+
+        record
+    }
+
+    #[test]
+    fn test_parses_records_skipping_zeros_and_corruption() {
+        // A sparse start, larger than the buffer.
+        let mut journal = vec![0; 3 * BUFFER_SIZE];
+
+        let first_offset = journal.len() as u64;
+        journal.extend(v2_record(
+            first_offset,
+            0x0001_0000_0000_0040,
+            5,
+            reason::FILE_CREATE,
+            "a.txt",
+        ));
+
+        // A partially overwritten record.
+        journal.extend(vec![0xff; 24]);
+
+        // A version 4 (range tracking) record, which is skipped.
+        let mut v4 = vec![0; 48];
+        v4[0..4].copy_from_slice(&48_u32.to_le_bytes());
+        v4[4..6].copy_from_slice(&4_u16.to_le_bytes());
+        journal.extend(v4);
+
+        journal.extend(vec![0; 1024]);
+        let third_offset = journal.len() as u64;
+        journal.extend(v3_record(third_offset, 0x41, "b.txt"));
+
+        let journal = UsnJournalFile::new(Cursor::new(journal)).unwrap();
+        let records: Vec<UsnRecord> = journal.map(|r| r.unwrap()).collect();
+
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].offset, first_offset);
+        assert_eq!(records[0].usn, first_offset);
+        assert_eq!(records[0].name, "a.txt");
+        assert_eq!(records[0].mft_entry_index(), 0x40);
+        assert_eq!(records[0].parent_file_reference, 5);
+        assert!(records[0].has_reason(reason::FILE_CREATE));
+        assert!(records[0].timestamp.is_some());
+
+        assert_eq!(records[1].offset, third_offset);
+        assert_eq!(records[1].major_version, 3);
+        assert_eq!(records[1].file_reference, 0x41);
+        assert_eq!(records[1].name, "b.txt");
+        assert!(records[1].has_reason(reason::CLOSE));
+        assert_eq!(records[1].path(), None);
+    }
+
+    #[test]
+    fn test_counts_skipped_bytes() {
+        let mut journal = vec![0; 4096];
+        journal.extend(vec![0xff; 16]);
+        journal.extend(v2_record(0, 0x40, 5, reason::CLOSE, "a"));
+
+        let mut journal = UsnJournalFile::new(Cursor::new(journal)).unwrap();
+
+        assert!(journal.next().unwrap().is_ok());
+        assert!(journal.next().is_none());
+        assert_eq!(journal.skipped_zero_bytes(), 4096);
+        assert_eq!(journal.skipped_corrupt_bytes(), 16);
+    }
+
+    #[test]
+    fn test_skips_records_with_odd_name_lengths() {
+        let mut corrupt = v2_record(0, 0x40, 5, reason::CLOSE, "a.txt");
+        corrupt[56..58].copy_from_slice(&9_u16.to_le_bytes());
+
+        assert!(UsnRecord::from_bytes(&corrupt, 0).is_err());
+
+        let mut journal = corrupt.clone();
+        let next_offset = journal.len() as u64;
+        journal.extend(v2_record(next_offset, 0x41, 5, reason::CLOSE, "b.txt"));
+
+        let mut journal = UsnJournalFile::new(Cursor::new(journal)).unwrap();
+        let record = journal.next().unwrap().unwrap();
+
+        assert_eq!(record.offset, next_offset);
+        assert_eq!(record.name, "b.txt");
+        assert!(journal.next().is_none());
+        assert_eq!(
+            journal.skipped_corrupt_bytes() + journal.skipped_zero_bytes(),
+            corrupt.len() as u64
+        );
+    }
+
+    /// Counts the bytes read from the journal.
+    struct CountingReader<R> {
+        inner: R,
+        read_bytes: u64,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.read_bytes += read as u64;
+            Ok(read)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_reads_the_journal_once() {
+        let mut journal = vec![0; 2 * BUFFER_SIZE + 24];
+
+        for i in 0..4096 {
+            let offset = journal.len() as u64;
+            journal.extend(v2_record(offset, 0x40 + i, 5, reason::CLOSE, "a.txt"));
+
+            // Some partially overwritten records, which are rescanned.
+            if i % 64 == 0 {
+                journal.extend(vec![0xff; 256]);
+            }
+        }
+
+        let reader = CountingReader {
+            inner: Cursor::new(journal.clone()),
+            read_bytes: 0,
+        };
+        let mut records = UsnJournalFile::new(reader).unwrap();
+
+        assert_eq!(records.by_ref().filter(|r| r.is_ok()).count(), 4096);
+        assert_eq!(records.skipped_corrupt_bytes(), 64 * 256);
+
+        // Only records which straddle the end of the buffer are read again.
+        let read_bytes = records.into_inner().read_bytes;
+        assert!(read_bytes < journal.len() as u64 + 64 * MAX_RECORD_LENGTH as u64);
+    }
+
+    #[test]
+    fn test_resolves_parent_paths() {
+        let volume = sample_volume().unwrap();
+        let root_reference = volume
+            .get_root_directory()
+            .unwrap()
+            .get_file_reference()
+            .unwrap();
+
+        let mut journal = v2_record(0, 0x40, root_reference, reason::CLOSE, "a.txt");
+        // The parent was reused since (its sequence number does not match).
+        journal.extend(v2_record(
+            0,
+            0x41,
+            0x00ff_0000_0000_0000,
+            reason::CLOSE,
+            "b.txt",
+        ));
+
+        let records: Vec<UsnRecord> = UsnJournalFile::new(Cursor::new(journal))
+            .unwrap()
+            .resolve_parent_paths(&volume)
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert_eq!(records[0].parent_path, Some("\\".to_string()));
+        assert_eq!(records[0].path(), Some("\\a.txt".to_string()));
+        assert_eq!(records[1].parent_path, None);
+    }
+}
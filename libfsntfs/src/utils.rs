@@ -1,4 +1,7 @@
+use crate::error::Error;
 use chrono::prelude::*;
+use libfsntfs_sys::off64_t;
+use std::io;
 use time::Duration;
 
 pub fn datetime_from_filetime(nanos_since_windows_epoch: u64) -> DateTime<Utc> {
@@ -8,3 +11,44 @@ pub fn datetime_from_filetime(nanos_since_windows_epoch: u64) -> DateTime<Utc> {
         Utc,
     )
 }
+
+pub fn u16_le(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+pub fn u32_le(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+pub fn u64_le(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// libfsntfs offsets are signed (`off64_t`), so larger offsets would be read as negative ones.
+pub fn signed_offset(offset: u64) -> Result<off64_t, Error> {
+    if offset > i64::MAX as u64 {
+        Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("offset {} is too large", offset),
+        )))
+    } else {
+        Ok(offset as off64_t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_offset() {
+        assert_eq!(signed_offset(0).unwrap(), 0);
+        assert_eq!(signed_offset(i64::MAX as u64).unwrap(), i64::MAX);
+        assert!(signed_offset(i64::MAX as u64 + 1).is_err());
+        assert!(signed_offset(u64::MAX).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::error::Error;
 use crate::ffi_error::{LibfsntfsError, LibfsntfsErrorRef, LibfsntfsErrorRefMut};
-use crate::file_entry::{FileEntry, FileEntryRef, FileEntryRefMut};
+use crate::file_entry::{FileEntry, FileEntryRef, FileEntryRefMut, SharedFileEntry};
+use crate::metadata::FileEntryMetadata;
+use crate::partition::read_partitions;
 use libbfio_rs::handle::{Handle, HandleRef};
 use libfsntfs_sys::{
     libfsntfs_file_entry_t, size32_t, LIBFSNTFS_ACCESS_FLAGS,
@@ -9,14 +11,18 @@ use libfsntfs_sys::{
 };
 use libyal_rs_common::ffi::AsTypeRef;
 use log::error;
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::ffi::{c_void, CStr, CString};
 use std::fs::File;
+use std::io::{self, Read};
 use std::marker::PhantomData;
 use std::mem;
+use std::ops::{Deref, Range};
 use std::os::raw::c_int;
 use std::path::{Iter, Path, PathBuf};
 use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 #[repr(C)]
 pub struct __Volume(isize);
@@ -24,8 +30,10 @@ pub struct __Volume(isize);
 pub type VolumeRefMut = *mut __Volume;
 pub type VolumeRef = *const __Volume;
 
+/// The volume owns the libbfio handle it was opened from (see `open_file_object`).
+/// libfsntfs keeps using the handle until the volume is closed, so it is dropped after the volume.
 #[repr(C)]
-pub struct Volume(VolumeRefMut);
+pub struct Volume(VolumeRefMut, Option<Handle>);
 
 impl AsTypeRef for Volume {
     type Ref = VolumeRef;
@@ -48,10 +56,17 @@ impl AsTypeRef for Volume {
 
 impl Volume {
     pub fn wrap_ptr(ptr: VolumeRefMut) -> Volume {
-        Volume(ptr)
+        Volume(ptr, None)
     }
 }
 
+// A `Volume` exclusively owns the libfsntfs volume and the `Send` handle it was opened from
+// (volumes are never opened from borrowed handles), and libfsntfs objects are not bound to
+// the thread that created them.
+// File entries borrow the volume and are not `Send`, so moving a volume requires that none exist.
+// It is not `Sync`, use `SyncVolume` to share a volume between threads.
+unsafe impl Send for Volume {}
+
 impl Drop for Volume {
     fn drop(&mut self) {
         let mut error = ptr::null_mut();
@@ -92,6 +107,14 @@ extern "C" {
         error: *mut LibfsntfsErrorRefMut,
     ) -> c_int;
     pub fn libfsntfs_volume_close(volume: VolumeRef, error: *mut LibfsntfsErrorRefMut) -> c_int;
+    pub fn libfsntfs_check_volume_signature(
+        filename: *const ::std::os::raw::c_char,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
+    pub fn libfsntfs_check_volume_signature_file_io_handle(
+        file_io_handle: HandleRef,
+        error: *mut LibfsntfsErrorRefMut,
+    ) -> c_int;
     pub fn libfsntfs_volume_has_bitlocker_drive_encryption(
         volume: VolumeRef,
         error: *mut LibfsntfsErrorRefMut,
@@ -192,7 +215,7 @@ pub enum AccessMode {
 }
 
 impl AccessMode {
-    fn as_flag(&self) -> LIBFSNTFS_ACCESS_FLAGS {
+    pub(crate) fn as_flag(&self) -> LIBFSNTFS_ACCESS_FLAGS {
         match self {
             AccessMode::Read => LIBFSNTFS_ACCESS_FLAGS_LIBFSNTFS_ACCESS_FLAG_READ,
             AccessMode::Write => LIBFSNTFS_ACCESS_FLAGS_LIBFSNTFS_ACCESS_FLAG_WRITE,
@@ -201,8 +224,64 @@ impl AccessMode {
 }
 pub type MftEntryIndex = u64;
 
+/// The lower 48 bits of a file reference are the MFT entry index (the rest is the sequence number).
+pub fn mft_entry_index_from_reference(file_reference: u64) -> MftEntryIndex {
+    file_reference & 0x0000_ffff_ffff_ffff
+}
+
 pub type SerialNumber = u64;
 
+/// The OEM identifier in the boot sector of a BitLocker encrypted volume.
+const BITLOCKER_SIGNATURE: &[u8; 8] = b"-FVE-FS-";
+
+/// What `Volume::probe` found in the boot sector of a volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeSignature {
+    Ntfs,
+    /// An encrypted volume, which can not be opened before it is decrypted.
+    BitLocker,
+    Unknown,
+}
+
+impl VolumeSignature {
+    /// Derives the signature from the result of `libfsntfs_check_volume_signature`,
+    /// only reading the boot sector when it is not an NTFS volume.
+    fn from_check_result(
+        retcode: c_int,
+        error: LibfsntfsErrorRefMut,
+        read_oem_identifier: impl FnOnce(&mut [u8]) -> io::Result<()>,
+    ) -> Result<Self, Error> {
+        match retcode {
+            1 => Ok(VolumeSignature::Ntfs),
+            0 => {
+                let mut boot_sector_start = [0; 11];
+                read_oem_identifier(&mut boot_sector_start).map_err(Error::Io)?;
+
+                if &boot_sector_start[3..11] == BITLOCKER_SIGNATURE {
+                    Ok(VolumeSignature::BitLocker)
+                } else {
+                    Ok(VolumeSignature::Unknown)
+                }
+            }
+            _ => Err(Error::try_from(error)?),
+        }
+    }
+
+    pub fn is_ntfs(&self) -> bool {
+        *self == VolumeSignature::Ntfs
+    }
+}
+
+/// The layout of the volume, as described by its boot sector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeGeometry {
+    pub cluster_block_size: usize,
+    pub mft_entry_size: u32,
+    pub index_entry_size: u32,
+    pub major_version: u8,
+    pub minor_version: u8,
+}
+
 pub struct IterFileEntries<'a> {
     handle: &'a Volume,
     number_of_file_entries: usize,
@@ -226,13 +305,308 @@ impl<'a> Iterator for IterFileEntries<'a> {
     }
 }
 
+/// What to do with MFT extension records
+/// (entries whose base record file reference points to another entry).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExtensionRecords {
+    /// Yield extension records like any other entry.
+    Include,
+    /// Do not yield extension records.
+    Skip,
+    /// Yield the base record in place of the extension record (each base record is yielded once).
+    /// libfsntfs already exposes the attributes of extension records through their base record.
+    Merge,
+}
+
+/// What to do when a file entry cannot be read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorPolicy {
+    /// Yield the error and continue with the next entry (like `Volume::iter_entries`).
+    Yield,
+    /// Ignore the error (it is still counted in the statistics).
+    Skip,
+    /// Keep the error (see `IterFileEntriesWithOptions::errors`) and continue with the next entry.
+    Collect,
+    /// Yield the error and stop.
+    Stop,
+}
+
+/// Options for `Volume::iter_entries_with_options`.
+#[derive(Debug, Clone)]
+pub struct IterOptions {
+    allocated: bool,
+    unallocated: bool,
+    skip_empty: bool,
+    extension_records: ExtensionRecords,
+    range: Option<Range<MftEntryIndex>>,
+    error_policy: ErrorPolicy,
+}
+
+impl Default for IterOptions {
+    fn default() -> Self {
+        IterOptions {
+            allocated: true,
+            unallocated: true,
+            skip_empty: false,
+            extension_records: ExtensionRecords::Include,
+            range: None,
+            error_policy: ErrorPolicy::Yield,
+        }
+    }
+}
+
+impl IterOptions {
+    /// The default options visit every entry, like `Volume::iter_entries`.
+    pub fn new() -> Self {
+        IterOptions::default()
+    }
+
+    /// Include allocated (in use) entries. Defaults to true.
+    pub fn allocated(mut self, allocated: bool) -> Self {
+        self.allocated = allocated;
+        self
+    }
+
+    /// Include unallocated (deleted) entries. Defaults to true.
+    pub fn unallocated(mut self, unallocated: bool) -> Self {
+        self.unallocated = unallocated;
+        self
+    }
+
+    /// Skip entries which were never used. Defaults to false.
+    pub fn skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// Defaults to `ExtensionRecords::Include`.
+    pub fn extension_records(mut self, extension_records: ExtensionRecords) -> Self {
+        self.extension_records = extension_records;
+        self
+    }
+
+    /// Only visit entries in the given range of MFT entry indexes. Defaults to all the entries.
+    pub fn range(mut self, range: Range<MftEntryIndex>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Defaults to `ErrorPolicy::Yield`.
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IterStatistics {
+    pub visited: u64,
+    pub yielded: u64,
+    pub skipped_allocated: u64,
+    pub skipped_unallocated: u64,
+    pub skipped_empty: u64,
+    pub skipped_extension_records: u64,
+    pub merged_extension_records: u64,
+    pub errors: u64,
+}
+
+/// The base records yielded in place of their extension records (see `ExtensionRecords::Merge`).
+#[derive(Debug)]
+struct MergedBaseRecords {
+    range: Range<MftEntryIndex>,
+    merged: HashSet<MftEntryIndex>,
+}
+
+impl MergedBaseRecords {
+    fn new(range: Range<MftEntryIndex>) -> Self {
+        MergedBaseRecords {
+            range,
+            merged: HashSet::new(),
+        }
+    }
+
+    /// Whether the base record at `base_idx` should be yielded in place of an extension record,
+    /// when the next entry to visit is at `next_idx`.
+    /// It was (or will be) visited on its own if it is already merged or is not in the range.
+    fn should_merge(&self, base_idx: MftEntryIndex, next_idx: MftEntryIndex) -> bool {
+        base_idx >= next_idx && self.range.contains(&base_idx) && !self.merged.contains(&base_idx)
+    }
+
+    fn insert(&mut self, base_idx: MftEntryIndex) {
+        self.merged.insert(base_idx);
+    }
+
+    /// Whether the entry at `idx` was already merged, in which case it is not visited again.
+    fn take(&mut self, idx: MftEntryIndex) -> bool {
+        self.merged.remove(&idx)
+    }
+}
+
+pub struct IterFileEntriesWithOptions<'a> {
+    handle: &'a Volume,
+    options: IterOptions,
+    idx: MftEntryIndex,
+    end: MftEntryIndex,
+    stopped: bool,
+    merged_base_records: MergedBaseRecords,
+    errors: Vec<(MftEntryIndex, Error)>,
+    statistics: IterStatistics,
+}
+
+impl<'a> IterFileEntriesWithOptions<'a> {
+    /// The statistics so far.
+    pub fn statistics(&self) -> &IterStatistics {
+        &self.statistics
+    }
+
+    /// The errors kept so far when using `ErrorPolicy::Collect`.
+    /// Use `Iterator::by_ref` to keep the iterator around while consuming it.
+    pub fn errors(&self) -> &[(MftEntryIndex, Error)] {
+        &self.errors
+    }
+
+    pub fn take_errors(&mut self) -> Vec<(MftEntryIndex, Error)> {
+        mem::replace(&mut self.errors, vec![])
+    }
+
+    /// Applies the allocation and emptiness filters, counting the skipped entries.
+    fn passes_filters(&mut self, entry: &FileEntry) -> Result<bool, Error> {
+        if self.options.skip_empty && entry.is_empty()? {
+            self.statistics.skipped_empty += 1;
+            return Ok(false);
+        }
+
+        if !(self.options.allocated && self.options.unallocated) {
+            if entry.is_allocated()? {
+                if !self.options.allocated {
+                    self.statistics.skipped_allocated += 1;
+                    return Ok(false);
+                }
+            } else if !self.options.unallocated {
+                self.statistics.skipped_unallocated += 1;
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn filter_entry(&mut self, idx: MftEntryIndex) -> Result<Option<FileEntry<'a>>, Error> {
+        let entry = self.handle.get_file_entry_by_mft_idx(idx)?;
+
+        if !self.passes_filters(&entry)? {
+            return Ok(None);
+        }
+
+        if self.options.extension_records == ExtensionRecords::Include {
+            return Ok(Some(entry));
+        }
+
+        let base_record_file_reference = entry.get_base_record_file_reference()?;
+        let base_idx = mft_entry_index_from_reference(base_record_file_reference);
+
+        if base_record_file_reference == 0 || base_idx == idx {
+            return Ok(Some(entry));
+        }
+
+        if self.options.extension_records == ExtensionRecords::Skip {
+            self.statistics.skipped_extension_records += 1;
+            return Ok(None);
+        }
+
+        if !self.merged_base_records.should_merge(base_idx, self.idx) {
+            return Ok(None);
+        }
+
+        let base_entry = self.handle.get_file_entry_by_mft_idx(base_idx)?;
+
+        // The entry of the base record was reused since (by an unrelated file),
+        // so this is a stale extension record, like those of unallocated entries can be.
+        if base_entry.get_file_reference()? != base_record_file_reference {
+            self.statistics.skipped_extension_records += 1;
+            return Ok(None);
+        }
+
+        self.merged_base_records.insert(base_idx);
+
+        // The base record is not visited on its own anymore, so it is filtered here.
+        if !self.passes_filters(&base_entry)? {
+            return Ok(None);
+        }
+
+        self.statistics.merged_extension_records += 1;
+
+        Ok(Some(base_entry))
+    }
+}
+
+impl<'a> Iterator for IterFileEntriesWithOptions<'a> {
+    type Item = Result<FileEntry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.stopped && self.idx < self.end {
+            let idx = self.idx;
+            self.idx += 1;
+
+            // Already yielded in place of one of its extension records.
+            if self.merged_base_records.take(idx) {
+                continue;
+            }
+
+            self.statistics.visited += 1;
+
+            match self.filter_entry(idx) {
+                Ok(Some(entry)) => {
+                    self.statistics.yielded += 1;
+                    return Some(Ok(entry));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.statistics.errors += 1;
+
+                    match self.options.error_policy {
+                        ErrorPolicy::Yield => return Some(Err(e)),
+                        ErrorPolicy::Skip => {}
+                        ErrorPolicy::Collect => self.errors.push((idx, e)),
+                        ErrorPolicy::Stop => {
+                            self.stopped = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
 impl<'a> Volume {
     /// Opens a volume by filename.
     pub fn open(filename: impl AsRef<str>, mode: AccessMode) -> Result<Self, Error> {
-        let mut handle = ptr::null_mut();
+        let volume = Volume::initialize()?;
+        volume.open_path(filename.as_ref(), &mode)?;
 
-        let c_string = CString::new(filename.as_ref()).map_err(Error::StringContainsNul)?;
+        Ok(volume)
+    }
+
+    /// Opens a volume using a libbfio handle, which is kept alive as long as the volume.
+    pub fn open_file_object(file_handle: Handle) -> Result<Self, Error> {
+        let mut volume = Volume::initialize()?;
+        volume.open_handle(&file_handle, &AccessMode::Read)?;
+        volume.set_owned_handle(file_handle);
 
+        Ok(volume)
+    }
+
+    /// The libbfio handle the volume was opened from, if it was opened with `open_file_object`.
+    pub fn get_file_object(&self) -> Option<&Handle> {
+        self.1.as_ref()
+    }
+
+    /// Creates a volume which is not open yet.
+    pub(crate) fn initialize() -> Result<Self, Error> {
+        let mut handle = ptr::null_mut();
         let mut init_error = ptr::null_mut();
 
         let retcode =
@@ -242,13 +616,16 @@ impl<'a> Volume {
             return Err(Error::try_from(init_error)?);
         }
 
-        let volume = Volume::wrap_ptr(handle);
+        Ok(Volume::wrap_ptr(handle))
+    }
 
+    pub(crate) fn open_path(&self, filename: &str, mode: &AccessMode) -> Result<(), Error> {
+        let c_string = CString::new(filename).map_err(Error::StringContainsNul)?;
         let mut error = ptr::null_mut();
 
         if unsafe {
             libfsntfs_volume_open(
-                volume.as_type_ref(),
+                self.as_type_ref(),
                 c_string.as_ptr(),
                 mode.as_flag() as c_int,
                 &mut error as _,
@@ -257,40 +634,77 @@ impl<'a> Volume {
         {
             Err(Error::try_from(error)?)
         } else {
-            Ok(volume)
+            Ok(())
         }
     }
 
-    pub fn open_file_object(file_handle: &Handle) -> Result<Self, Error> {
-        let mut volume_handle = ptr::null_mut();
-        let mut init_error = ptr::null_mut();
-
-        let retcode =
-            unsafe { libfsntfs_volume_initialize(&mut volume_handle as _, &mut init_error as _) };
-
-        if retcode != 1 {
-            return Err(Error::try_from(init_error)?);
-        }
-
-        let volume = Volume::wrap_ptr(volume_handle);
-
+    pub(crate) fn open_handle(&self, file_handle: &Handle, mode: &AccessMode) -> Result<(), Error> {
         let mut error = ptr::null_mut();
 
         if unsafe {
             libfsntfs_volume_open_file_io_handle(
-                volume.as_type_ref(),
+                self.as_type_ref(),
                 file_handle.as_type_ref(),
-                1_u8,
+                mode.as_flag() as u8,
                 &mut error as _,
             )
         } != 1
         {
             Err(Error::try_from(error)?)
         } else {
-            Ok(volume)
+            Ok(())
         }
     }
 
+    /// Makes the volume own the handle it was opened with.
+    pub(crate) fn set_owned_handle(&mut self, file_handle: Handle) {
+        self.1 = Some(file_handle);
+    }
+
+    /// Opens the volume in the partition at `index` (see `partition::read_partitions`)
+    /// of a disk image.
+    pub fn open_partition(mut disk_handle: Handle, index: usize) -> Result<Self, Error> {
+        let partition = read_partitions(&mut disk_handle)?
+            .into_iter()
+            .find(|p| p.index == index)
+            .ok_or_else(|| Error::Other(format!("Partition {} does not exist", index)))?;
+
+        let partition_handle = Handle::open_window(disk_handle, partition.offset, partition.size)?;
+
+        Volume::open_file_object(partition_handle)
+    }
+
+    /// Checks the boot sector of the volume at `filename`, which is much cheaper than opening it.
+    pub fn probe(filename: impl AsRef<str>) -> Result<VolumeSignature, Error> {
+        let c_string = CString::new(filename.as_ref()).map_err(Error::StringContainsNul)?;
+        let mut error = ptr::null_mut();
+
+        let retcode = unsafe { libfsntfs_check_volume_signature(c_string.as_ptr(), &mut error) };
+
+        VolumeSignature::from_check_result(retcode, error, |buf| {
+            File::open(filename.as_ref())?.read_exact(buf)
+        })
+    }
+
+    /// Checks the boot sector of the volume in a libbfio handle, see `probe`.
+    pub fn probe_handle(file_handle: &Handle) -> Result<VolumeSignature, Error> {
+        let mut error = ptr::null_mut();
+
+        let retcode = unsafe {
+            libfsntfs_check_volume_signature_file_io_handle(file_handle.as_type_ref(), &mut error)
+        };
+
+        VolumeSignature::from_check_result(retcode, error, |buf| {
+            let count = file_handle.read_at(buf, 0)?;
+
+            if count < buf.len() {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            Ok(())
+        })
+    }
+
     pub fn iter_entries(&self) -> Result<IterFileEntries, Error> {
         Ok(IterFileEntries {
             handle: self,
@@ -299,6 +713,36 @@ impl<'a> Volume {
         })
     }
 
+    /// Iterates over the file entries, filtered and with errors handled according to `options`.
+    pub fn iter_entries_with_options(
+        &self,
+        options: IterOptions,
+    ) -> Result<IterFileEntriesWithOptions, Error> {
+        let number_of_file_entries = self.get_number_of_file_entries()? as MftEntryIndex;
+
+        let end = options
+            .range
+            .as_ref()
+            .map(|range| range.end.min(number_of_file_entries))
+            .unwrap_or(number_of_file_entries);
+        let start = options
+            .range
+            .as_ref()
+            .map(|range| range.start.min(end))
+            .unwrap_or(0);
+
+        Ok(IterFileEntriesWithOptions {
+            handle: self,
+            options,
+            idx: start,
+            end,
+            stopped: false,
+            merged_base_records: MergedBaseRecords::new(start..end),
+            errors: vec![],
+            statistics: IterStatistics::default(),
+        })
+    }
+
     /// Retrieves the volume serial number.
     pub fn get_serial_number(&self) -> Result<SerialNumber, Error> {
         let mut serial_number = 0_u64;
@@ -314,8 +758,123 @@ impl<'a> Volume {
         }
     }
 
+    /// Retrieves the cluster block size.
+    pub fn get_cluster_block_size(&self) -> Result<usize, Error> {
+        let mut cluster_block_size = 0_usize;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_volume_get_cluster_block_size(
+                self.as_type_ref(),
+                &mut cluster_block_size,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok(cluster_block_size)
+        }
+    }
+
+    /// Retrieves the MFT entry size.
+    pub fn get_mft_entry_size(&self) -> Result<u32, Error> {
+        get_u32_field!(self, libfsntfs_volume_get_mft_entry_size)
+    }
+
+    /// Retrieves the index entry size.
+    pub fn get_index_entry_size(&self) -> Result<u32, Error> {
+        get_u32_field!(self, libfsntfs_volume_get_index_entry_size)
+    }
+
+    /// Retrieves the NTFS version as (major, minor).
+    pub fn get_version(&self) -> Result<(u8, u8), Error> {
+        let mut major_version = 0_u8;
+        let mut minor_version = 0_u8;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_volume_get_version(
+                self.as_type_ref(),
+                &mut major_version,
+                &mut minor_version,
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            Ok((major_version, minor_version))
+        }
+    }
+
+    /// Retrieves the cluster, MFT entry and index entry sizes and the version.
+    pub fn get_geometry(&self) -> Result<VolumeGeometry, Error> {
+        let (major_version, minor_version) = self.get_version()?;
+
+        Ok(VolumeGeometry {
+            cluster_block_size: self.get_cluster_block_size()?,
+            mft_entry_size: self.get_mft_entry_size()?,
+            index_entry_size: self.get_index_entry_size()?,
+            major_version,
+            minor_version,
+        })
+    }
+
+    /// Determines if the volume has BitLocker Drive Encryption.
+    pub fn has_bitlocker(&self) -> Result<bool, Error> {
+        get_bool_field!(self, libfsntfs_volume_has_bitlocker_drive_encryption)
+    }
+
+    /// Determines if the volume has Volume Shadow Snapshots.
+    pub fn has_volume_shadow_snapshots(&self) -> Result<bool, Error> {
+        get_bool_field!(self, libfsntfs_volume_has_volume_shadow_snapshots)
+    }
+
+    /// Retrieves the name as UTF-16 (without a nul terminator).
+    pub fn get_utf16_name(&self) -> Result<Vec<u16>, Error> {
+        let mut name_size = 0_usize;
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_volume_get_utf16_name_size(self.as_type_ref(), &mut name_size, &mut error)
+        } != 1
+        {
+            return Err(Error::try_from(error)?);
+        }
+
+        if name_size == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut name = vec![0_u16; name_size];
+        let mut error = ptr::null_mut();
+
+        if unsafe {
+            libfsntfs_volume_get_utf16_name(
+                self.as_type_ref(),
+                name.as_mut_ptr(),
+                name.len(),
+                &mut error,
+            )
+        } != 1
+        {
+            Err(Error::try_from(error)?)
+        } else {
+            // Discard nul terminator;
+            name.pop();
+            Ok(name)
+        }
+    }
+
     /// Retrieves a file entry specified by the path.
     pub fn get_file_entry_by_path(&self, path: impl AsRef<Path>) -> Result<FileEntry, Error> {
+        let file_entry = self.get_file_entry_ptr_by_path(path)?;
+
+        Ok(FileEntry::wrap_ptr(self, file_entry))
+    }
+
+    fn get_file_entry_ptr_by_path(&self, path: impl AsRef<Path>) -> Result<FileEntryRefMut, Error> {
         let mut file_entry = ptr::null_mut();
         let mut error = ptr::null_mut();
 
@@ -336,12 +895,18 @@ impl<'a> Volume {
         {
             Err(Error::try_from(error)?)
         } else {
-            Ok(FileEntry::wrap_ptr(self, file_entry))
+            Ok(file_entry)
         }
     }
 
     /// Retrieves a specific file entry.
     pub fn get_file_entry_by_mft_idx(&self, idx: MftEntryIndex) -> Result<FileEntry, Error> {
+        let file_entry = self.get_file_entry_ptr_by_mft_idx(idx)?;
+
+        Ok(FileEntry::wrap_ptr(self, file_entry))
+    }
+
+    fn get_file_entry_ptr_by_mft_idx(&self, idx: MftEntryIndex) -> Result<FileEntryRefMut, Error> {
         let mut file_entry = ptr::null_mut();
         let mut error = ptr::null_mut();
 
@@ -356,7 +921,7 @@ impl<'a> Volume {
         {
             Err(Error::try_from(error)?)
         } else {
-            Ok(FileEntry::wrap_ptr(self, file_entry))
+            Ok(file_entry)
         }
     }
 
@@ -376,6 +941,12 @@ impl<'a> Volume {
 
     /// Retrieves the root directory.
     pub fn get_root_directory(&self) -> Result<FileEntry, Error> {
+        let file_entry = self.get_root_directory_ptr()?;
+
+        Ok(FileEntry::wrap_ptr(self, file_entry))
+    }
+
+    fn get_root_directory_ptr(&self) -> Result<FileEntryRefMut, Error> {
         let mut file_entry = ptr::null_mut();
         let mut error = ptr::null_mut();
 
@@ -385,7 +956,7 @@ impl<'a> Volume {
         {
             Err(Error::try_from(error)?)
         } else {
-            Ok(FileEntry::wrap_ptr(self, file_entry))
+            Ok(file_entry)
         }
     }
 
@@ -419,17 +990,176 @@ impl<'a> Volume {
     }
 }
 
+/// A reference counted `Volume`.
+///
+/// File entries retrieved from a `SharedVolume` keep the volume alive,
+/// so they (and their attributes) are not bound to the lifetime of a borrow.
+/// The borrowing API of `Volume` is available through `Deref`.
+#[derive(Clone)]
+pub struct SharedVolume(Arc<Volume>);
+
+impl From<Volume> for SharedVolume {
+    fn from(volume: Volume) -> Self {
+        SharedVolume(Arc::new(volume))
+    }
+}
+
+impl Deref for SharedVolume {
+    type Target = Volume;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub struct IterSharedFileEntries {
+    handle: SharedVolume,
+    number_of_file_entries: usize,
+    idx: usize,
+}
+
+impl Iterator for IterSharedFileEntries {
+    type Item = Result<SharedFileEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx < self.number_of_file_entries {
+            let entry = self
+                .handle
+                .get_file_entry_by_mft_idx(self.idx as MftEntryIndex);
+            self.idx += 1;
+
+            return Some(entry);
+        }
+
+        None
+    }
+}
+
+impl SharedVolume {
+    /// Opens a volume by filename.
+    pub fn open(filename: impl AsRef<str>, mode: AccessMode) -> Result<Self, Error> {
+        Ok(Volume::open(filename, mode)?.into())
+    }
+
+    /// Opens a volume using a libbfio handle, which is kept alive as long as the volume.
+    pub fn open_file_object(file_handle: Handle) -> Result<Self, Error> {
+        Ok(Volume::open_file_object(file_handle)?.into())
+    }
+
+    pub fn iter_entries(&self) -> Result<IterSharedFileEntries, Error> {
+        Ok(IterSharedFileEntries {
+            handle: self.clone(),
+            number_of_file_entries: self.get_number_of_file_entries()?,
+            idx: 0,
+        })
+    }
+
+    /// Retrieves a file entry specified by the path.
+    pub fn get_file_entry_by_path(&self, path: impl AsRef<Path>) -> Result<SharedFileEntry, Error> {
+        let file_entry = self.0.get_file_entry_ptr_by_path(path)?;
+
+        Ok(SharedFileEntry::wrap_ptr(self.0.clone(), file_entry))
+    }
+
+    /// Retrieves a specific file entry.
+    pub fn get_file_entry_by_mft_idx(&self, idx: MftEntryIndex) -> Result<SharedFileEntry, Error> {
+        let file_entry = self.0.get_file_entry_ptr_by_mft_idx(idx)?;
+
+        Ok(SharedFileEntry::wrap_ptr(self.0.clone(), file_entry))
+    }
+
+    /// Retrieves the root directory.
+    pub fn get_root_directory(&self) -> Result<SharedFileEntry, Error> {
+        let file_entry = self.0.get_root_directory_ptr()?;
+
+        Ok(SharedFileEntry::wrap_ptr(self.0.clone(), file_entry))
+    }
+}
+
+/// A `Volume` which can be shared between threads (it is `Send` and `Sync`).
+///
+/// Access to the volume is serialized with a mutex, and file entries (which borrow the volume)
+/// can only be used while holding it, using `with`.
+/// This does not depend on libfsntfs being built with multi-threading support.
+pub struct SyncVolume(Mutex<Volume>);
+
+impl From<Volume> for SyncVolume {
+    fn from(volume: Volume) -> Self {
+        SyncVolume(Mutex::new(volume))
+    }
+}
+
+impl SyncVolume {
+    /// Opens a volume by filename.
+    pub fn open(filename: impl AsRef<str>, mode: AccessMode) -> Result<Self, Error> {
+        Ok(Volume::open(filename, mode)?.into())
+    }
+
+    /// Opens a volume using a libbfio handle, which is kept alive as long as the volume.
+    pub fn open_file_object(file_handle: Handle) -> Result<Self, Error> {
+        Ok(Volume::open_file_object(file_handle)?.into())
+    }
+
+    fn lock(&self) -> MutexGuard<Volume> {
+        // A panic while holding the lock cannot leave libfsntfs in the middle of a call,
+        // so the volume is still usable.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Runs `f` with exclusive access to the volume.
+    pub fn with<T>(&self, f: impl FnOnce(&Volume) -> T) -> T {
+        f(&self.lock())
+    }
+
+    /// Retrieves the number of file entries.
+    pub fn get_number_of_file_entries(&self) -> Result<usize, Error> {
+        self.lock().get_number_of_file_entries()
+    }
+
+    /// Retrieves the metadata of a specific file entry.
+    pub fn get_file_entry_metadata(&self, idx: MftEntryIndex) -> Result<FileEntryMetadata, Error> {
+        self.lock().get_file_entry_by_mft_idx(idx)?.metadata()
+    }
+
+    /// Reads data of a specific file entry at the given offset.
+    pub fn read_file_entry_at(
+        &self,
+        idx: MftEntryIndex,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> Result<usize, Error> {
+        self.lock()
+            .get_file_entry_by_mft_idx(idx)?
+            .read_at(buf, offset)
+    }
+
+    pub fn into_inner(self) -> Volume {
+        self.0
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::fixtures::*;
+    use crate::partition::tests::set_mbr_entry;
+    use crate::partition::SECTOR_SIZE;
+    use libbfio_rs::faults::FaultInjector;
+    use libbfio_rs::handle::LibbfioAccessFlags;
+    use libbfio_rs::tolerant::TolerantReader;
     use log::{info, trace};
+    use std::fs;
     use std::path::PathBuf;
+    use tempdir::TempDir;
 
     #[test]
     fn test_opens_volume_file_io_works() {
         let handle = sample_volume_io_handle().unwrap();
-        let sample_volume_from_io = Volume::open_file_object(&handle).unwrap();
+        let sample_volume_from_io = Volume::open_file_object(handle).unwrap();
 
         let volume_name_from_disk = sample_volume().unwrap().get_name().unwrap();
         let volume_name_from_io_handle = sample_volume_from_io.get_name().unwrap();
@@ -437,6 +1167,182 @@ mod tests {
         assert_eq!(volume_name_from_disk, volume_name_from_io_handle)
     }
 
+    #[test]
+    fn test_opens_volume_in_partition() {
+        let dir = TempDir::new("libfsntfs").unwrap();
+        let disk_path = dir.path().join("disk.dd");
+
+        // A disk with the sample volume in its first partition, which starts after 1MiB.
+        let partition = fs::read(sample_volume_path()).unwrap();
+        let mut disk = vec![0; 2048 * SECTOR_SIZE as usize];
+        set_mbr_entry(
+            &mut disk,
+            0,
+            0,
+            0x07,
+            2048,
+            (partition.len() as u64 / SECTOR_SIZE) as u32,
+        );
+        disk.extend(partition);
+        fs::write(&disk_path, disk).unwrap();
+
+        let handle = Handle::open_file(&disk_path, LibbfioAccessFlags::Read).unwrap();
+        let volume = Volume::open_partition(handle, 0).unwrap();
+
+        assert_eq!(
+            volume.get_name().unwrap(),
+            sample_volume().unwrap().get_name().unwrap()
+        );
+        assert!(volume.get_file_entry_by_mft_idx(0).is_ok());
+
+        let handle = Handle::open_file(&disk_path, LibbfioAccessFlags::Read).unwrap();
+        assert!(Volume::open_partition(handle, 1).is_err());
+    }
+
+    #[test]
+    fn test_probe() {
+        assert_eq!(
+            Volume::probe(sample_volume_path()).unwrap(),
+            VolumeSignature::Ntfs
+        );
+
+        let handle = sample_volume_io_handle().unwrap();
+        assert!(Volume::probe_handle(&handle).unwrap().is_ntfs());
+    }
+
+    #[test]
+    fn test_probe_detects_bitlocker() {
+        let dir = TempDir::new("libfsntfs").unwrap();
+        let path = dir.path().join("volume.dd");
+
+        let mut boot_sector = vec![0; SECTOR_SIZE as usize * 16];
+        fs::write(&path, &boot_sector).unwrap();
+        assert_eq!(
+            Volume::probe(path.to_str().unwrap()).unwrap(),
+            VolumeSignature::Unknown
+        );
+
+        boot_sector[3..11].copy_from_slice(b"-FVE-FS-");
+        fs::write(&path, &boot_sector).unwrap();
+        assert_eq!(
+            Volume::probe(path.to_str().unwrap()).unwrap(),
+            VolumeSignature::BitLocker
+        );
+
+        let handle = Handle::open_file(&path, LibbfioAccessFlags::Read).unwrap();
+        assert_eq!(
+            Volume::probe_handle(&handle).unwrap(),
+            VolumeSignature::BitLocker
+        );
+    }
+
+    #[test]
+    fn test_opens_volume_from_in_memory_reader() {
+        let image = fs::read(sample_volume_path()).unwrap();
+        let handle = Handle::from_reader(std::io::Cursor::new(image)).unwrap();
+        let volume = Volume::open_file_object(handle).unwrap();
+
+        assert_eq!(
+            volume.get_name().unwrap(),
+            sample_volume().unwrap().get_name().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_opens_volume_embedded_in_container() {
+        let image = fs::read(sample_volume_path()).unwrap();
+
+        let mut container = vec![0xAA; 4096];
+        container.extend(&image);
+        container.extend(vec![0xBB; 4096]);
+
+        let handle =
+            Handle::open_window(std::io::Cursor::new(container), 4096, image.len() as u64).unwrap();
+        assert_eq!(handle.get_size().unwrap(), image.len() as u64);
+
+        let volume = Volume::open_file_object(handle).unwrap();
+
+        assert_eq!(
+            volume.get_name().unwrap(),
+            sample_volume().unwrap().get_name().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_open_fails_on_read_errors() {
+        let image = fs::read(sample_volume_path()).unwrap();
+        let source = FaultInjector::new(std::io::Cursor::new(image)).fail_reads_in(0..512);
+        let handle = Handle::from_reader(source).unwrap();
+
+        assert!(Volume::open_file_object(handle).is_err());
+    }
+
+    #[test]
+    fn test_read_errors_after_open_are_returned() {
+        let image = fs::read(sample_volume_path()).unwrap();
+
+        // Count the reads it takes to open the volume, and fail all the reads after them.
+        let handle = Handle::from_reader(std::io::Cursor::new(image.clone())).unwrap();
+        handle.reset_io_stats().unwrap();
+        let volume = Volume::open_file_object(handle).unwrap();
+        let reads_to_open = volume.get_file_object().unwrap().io_stats().unwrap().reads;
+        drop(volume);
+
+        let source =
+            FaultInjector::new(std::io::Cursor::new(image)).fail_reads_after(reads_to_open);
+        let volume = Volume::open_file_object(Handle::from_reader(source).unwrap()).unwrap();
+
+        assert!(volume.iter_entries().unwrap().any(|entry| entry.is_err()));
+    }
+
+    #[test]
+    fn test_opens_volume_with_bad_sectors() {
+        let image = fs::read(sample_volume_path()).unwrap();
+        let last_sector = image.len() as u64 - SECTOR_SIZE;
+
+        let source = FaultInjector::new(std::io::Cursor::new(image.clone()))
+            .fail_reads_in(last_sector..last_sector + 1);
+        let reader = TolerantReader::new(source).unwrap();
+        let bad_ranges = reader.bad_ranges();
+        let handle = Handle::from_reader(reader).unwrap();
+
+        let volume = Volume::open_file_object(handle).unwrap();
+        assert_eq!(
+            volume.get_name().unwrap(),
+            sample_volume().unwrap().get_name().unwrap()
+        );
+
+        let mut buf = vec![0xFF; SECTOR_SIZE as usize];
+        volume
+            .get_file_object()
+            .unwrap()
+            .read_at(&mut buf, last_sector)
+            .unwrap();
+
+        assert!(buf.iter().all(|b| *b == 0));
+        assert_eq!(bad_ranges.to_vec(), vec![last_sector..image.len() as u64]);
+    }
+
+    #[test]
+    fn test_opens_volume_from_split_image() {
+        let dir = TempDir::new("libfsntfs").unwrap();
+        let image = fs::read(sample_volume_path()).unwrap();
+
+        let segment_size = (image.len() / 3 + 1) as usize;
+        for (i, segment) in image.chunks(segment_size).enumerate() {
+            fs::write(dir.path().join(format!("image.{:03}", i + 1)), segment).unwrap();
+        }
+
+        let handle = Handle::open_segments(dir.path().join("image.001")).unwrap();
+        let volume = Volume::open_file_object(handle).unwrap();
+
+        assert_eq!(
+            volume.get_name().unwrap(),
+            sample_volume().unwrap().get_name().unwrap()
+        );
+        assert!(volume.get_file_entry_by_mft_idx(0).is_ok());
+    }
+
     #[test]
     fn test_opens_volume_works() {
         assert!(sample_volume().is_ok());
@@ -471,4 +1377,233 @@ mod tests {
             println!("{:?}", entry);
         }
     }
+
+    #[test]
+    fn test_get_geometry() {
+        let geometry = sample_volume().unwrap().get_geometry().unwrap();
+
+        assert!(geometry.cluster_block_size.is_power_of_two());
+        assert!(geometry.cluster_block_size >= 512);
+        assert_eq!(geometry.mft_entry_size, 1024);
+        assert_eq!(geometry.index_entry_size, 4096);
+        assert_eq!(geometry.major_version, 3);
+    }
+
+    #[test]
+    fn test_volume_features() {
+        let volume = sample_volume().unwrap();
+
+        assert!(!volume.has_bitlocker().unwrap());
+        assert!(!volume.has_volume_shadow_snapshots().unwrap());
+    }
+
+    #[test]
+    fn test_get_utf16_name() {
+        let name = sample_volume().unwrap().get_utf16_name().unwrap();
+
+        assert_eq!(String::from_utf16(&name).unwrap(), "KW-SRCH-1");
+    }
+
+    #[test]
+    fn test_iter_entries_with_default_options() {
+        let volume = sample_volume().unwrap();
+
+        let mut iter = volume
+            .iter_entries_with_options(IterOptions::new())
+            .unwrap();
+        let count = iter.by_ref().count();
+
+        assert_eq!(count, volume.get_number_of_file_entries().unwrap());
+        assert_eq!(iter.statistics().visited, count as u64);
+    }
+
+    #[test]
+    fn test_iter_entries_with_options_filters() {
+        let volume = sample_volume().unwrap();
+
+        let options = IterOptions::new()
+            .unallocated(false)
+            .skip_empty(true)
+            .extension_records(ExtensionRecords::Skip)
+            .error_policy(ErrorPolicy::Collect);
+
+        let mut iter = volume.iter_entries_with_options(options).unwrap();
+
+        for entry in iter.by_ref() {
+            let entry = entry.expect("errors should be collected");
+
+            assert!(entry.is_allocated().unwrap());
+            assert!(!entry.is_empty().unwrap());
+            assert_eq!(entry.get_base_record_file_reference().unwrap(), 0);
+        }
+
+        let statistics = iter.statistics().clone();
+        assert_eq!(statistics.errors, iter.errors().len() as u64);
+        assert_eq!(
+            statistics.visited,
+            statistics.yielded
+                + statistics.skipped_unallocated
+                + statistics.skipped_empty
+                + statistics.skipped_extension_records
+                + statistics.errors
+        );
+    }
+
+    #[test]
+    fn test_merged_base_records() {
+        let mut merged = MergedBaseRecords::new(10..20);
+
+        // A base record after the extension record is merged once.
+        assert!(merged.should_merge(15, 12));
+        merged.insert(15);
+        assert!(!merged.should_merge(15, 13));
+
+        // Base records which were already visited, or are out of the range, are not merged.
+        assert!(!merged.should_merge(11, 12));
+        assert!(!merged.should_merge(5, 12));
+        assert!(!merged.should_merge(20, 12));
+
+        assert!(merged.take(15));
+        assert!(!merged.take(15));
+        assert!(!merged.take(16));
+    }
+
+    #[test]
+    fn test_iter_entries_merging_extension_records() {
+        let volume = sample_volume().unwrap();
+
+        let options = IterOptions::new()
+            .allocated(false)
+            .extension_records(ExtensionRecords::Merge);
+        let mut iter = volume.iter_entries_with_options(options).unwrap();
+        let mut file_references = HashSet::new();
+
+        for entry in iter.by_ref() {
+            let entry = entry.unwrap();
+            let file_reference = entry.get_file_reference().unwrap();
+            let base_record_file_reference = entry.get_base_record_file_reference().unwrap();
+
+            // Merged base records are filtered like any other entry, and yielded once.
+            assert!(!entry.is_allocated().unwrap());
+            assert!(
+                base_record_file_reference == 0 || base_record_file_reference == file_reference
+            );
+            assert!(file_references.insert(file_reference));
+        }
+
+        let statistics = iter.statistics();
+        assert_eq!(statistics.yielded, file_references.len() as u64);
+        assert!(statistics.merged_extension_records <= statistics.yielded);
+    }
+
+    #[test]
+    fn test_iter_entries_with_range() {
+        let volume = sample_volume().unwrap();
+
+        let options = IterOptions::new().range(5..10);
+        let mut iter = volume.iter_entries_with_options(options).unwrap();
+
+        let root = iter.next().unwrap().unwrap();
+        assert_eq!(
+            mft_entry_index_from_reference(root.get_file_reference().unwrap()),
+            5
+        );
+        assert_eq!(iter.count(), 4);
+    }
+
+    #[test]
+    fn test_volume_opened_from_file_object_moves_to_threads() {
+        let volume = Volume::open_file_object(sample_volume_io_handle().unwrap()).unwrap();
+
+        // The handle moves with the volume, it can't be used (or dropped) on this thread.
+        let name = std::thread::spawn(move || volume.get_name().unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(name, "KW-SRCH-1")
+    }
+
+    #[test]
+    fn test_shared_volume_entries_outlive_volume() {
+        let volume = SharedVolume::open_file_object(sample_volume_io_handle().unwrap()).unwrap();
+
+        let entries: Vec<SharedFileEntry> = volume
+            .iter_entries()
+            .unwrap()
+            .take(16)
+            .filter_map(|e| e.ok())
+            .collect();
+        let root = volume.get_root_directory().unwrap();
+
+        drop(volume);
+
+        assert_eq!(entries[0].get_name().unwrap(), "$MFT");
+        assert!(root.get_number_of_sub_file_entries().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_shared_attributes_are_static() {
+        fn assert_static<T: 'static>(t: T) -> T {
+            t
+        }
+
+        let volume = SharedVolume::open(sample_volume_path(), AccessMode::Read).unwrap();
+
+        let attributes: Vec<_> = {
+            let entry = volume.get_file_entry_by_mft_idx(0).unwrap();
+            entry
+                .iter_attributes()
+                .unwrap()
+                .map(|a| assert_static(a.unwrap()))
+                .collect()
+        };
+
+        drop(volume);
+
+        assert!(attributes.iter().all(|a| a.get_type().is_ok()));
+    }
+
+    #[test]
+    fn test_sync_volume_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<SyncVolume>();
+    }
+
+    #[test]
+    fn test_sync_volume_concurrent_access() {
+        use std::thread;
+
+        let volume = Arc::new(SyncVolume::open(sample_volume_path(), AccessMode::Read).unwrap());
+        let number_of_file_entries = volume.get_number_of_file_entries().unwrap();
+
+        let read_entry = |volume: &SyncVolume, idx: MftEntryIndex| {
+            let metadata = volume.get_file_entry_metadata(idx).ok()?;
+            let mut buf = vec![0; metadata.size.min(4096) as usize];
+            let count = volume.read_file_entry_at(idx, &mut buf, 0).ok()?;
+            buf.truncate(count);
+
+            Some((metadata.name, buf))
+        };
+
+        let expected: Vec<_> = (0..number_of_file_entries)
+            .map(|idx| read_entry(&volume, idx as MftEntryIndex))
+            .collect();
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let volume = volume.clone();
+
+                thread::spawn(move || {
+                    (0..number_of_file_entries)
+                        .map(|idx| read_entry(&volume, idx as MftEntryIndex))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        for t in threads {
+            assert_eq!(t.join().unwrap(), expected);
+        }
+    }
 }
@@ -0,0 +1,353 @@
+//! Open-time configuration of a `Volume`.
+use crate::codepage::{set_codepage, Codepage};
+use crate::error::Error;
+use crate::notify;
+use crate::volume::{libfsntfs_volume_signal_abort, AccessMode, Volume, VolumeRef};
+use libbfio_rs::cache::CacheOptions;
+use libbfio_rs::handle::{Handle, LibbfioAccessFlags};
+use libyal_rs_common::ffi::AsTypeRef;
+use log::error;
+use std::io::{Read, Seek, SeekFrom};
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Options for opening a volume.
+#[derive(Debug, Clone)]
+pub struct VolumeOptions {
+    codepage: Option<Codepage>,
+    partition_offset: u64,
+    read_only: bool,
+    abort_handle: Option<AbortHandle>,
+    verbose: Option<bool>,
+    cache: Option<CacheOptions>,
+}
+
+impl Default for VolumeOptions {
+    fn default() -> Self {
+        VolumeOptions {
+            codepage: None,
+            partition_offset: 0,
+            read_only: true,
+            abort_handle: None,
+            verbose: None,
+            cache: None,
+        }
+    }
+}
+
+impl VolumeOptions {
+    pub fn new() -> Self {
+        VolumeOptions::default()
+    }
+
+    /// The codepage of ASCII and 8.3 names.
+    /// libfsntfs only has a process-wide codepage, so this affects all the volumes.
+    pub fn codepage(mut self, codepage: Codepage) -> Self {
+        self.codepage = Some(codepage);
+        self
+    }
+
+    /// The byte offset of the volume in the source (like a partition in a disk image).
+    /// Defaults to 0.
+    pub fn partition_offset(mut self, partition_offset: u64) -> Self {
+        self.partition_offset = partition_offset;
+        self
+    }
+
+    /// Open the volume for reading only. Defaults to true.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Allows aborting the open from another thread.
+    pub fn abort_handle(mut self, abort_handle: &AbortHandle) -> Self {
+        self.abort_handle = Some(abort_handle.clone());
+        self
+    }
+
+    /// Enables libfsntfs' verbose output (see `notify::set_verbose`), which is process-wide.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = Some(verbose);
+        self
+    }
+
+    /// Reads the volume file through a block cache (see `libbfio_rs::cache`),
+    /// which speeds up parsing on slow storage. Only supported for read-only volumes.
+    pub fn cache(mut self, options: CacheOptions) -> Self {
+        self.cache = Some(options);
+        self
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        if self.read_only {
+            AccessMode::Read
+        } else {
+            AccessMode::Write
+        }
+    }
+
+    /// Opens the volume by filename.
+    pub fn open(&self, filename: impl AsRef<str>) -> Result<Volume, Error> {
+        let file_handle = match &self.cache {
+            Some(_) if !self.read_only => {
+                return Err(Error::Other(
+                    "A block cache can only be used with read-only volumes".to_owned(),
+                ));
+            }
+            Some(options) => {
+                Handle::open_file_cached(filename.as_ref(), LibbfioAccessFlags::Read, options)
+            }
+            None if self.partition_offset == 0 => {
+                return self
+                    .open_with(|volume| volume.open_path(filename.as_ref(), &self.access_mode()));
+            }
+            None => Handle::open_file(filename.as_ref(), LibbfioAccessFlags::Read),
+        }?;
+
+        self.open_file_object(file_handle)
+    }
+
+    /// Opens the volume using a libbfio handle, which is kept alive as long as the volume.
+    pub fn open_file_object(&self, file_handle: Handle) -> Result<Volume, Error> {
+        let file_handle = self.apply_partition_offset(file_handle)?;
+
+        let mut volume =
+            self.open_with(|volume| volume.open_handle(&file_handle, &self.access_mode()))?;
+        volume.set_owned_handle(file_handle);
+
+        Ok(volume)
+    }
+
+    /// Opens the volume from any seekable reader, like an in-memory image.
+    pub fn open_reader<R: Read + Seek + Send + 'static>(&self, reader: R) -> Result<Volume, Error> {
+        if !self.read_only {
+            return Err(Error::Other(
+                "Volumes opened from a reader are read-only".to_owned(),
+            ));
+        }
+
+        let file_handle = Handle::from_reader(reader)?;
+
+        self.open_file_object(file_handle)
+    }
+
+    fn apply_partition_offset(&self, mut file_handle: Handle) -> Result<Handle, Error> {
+        if self.partition_offset == 0 {
+            return Ok(file_handle);
+        }
+
+        if !self.read_only {
+            return Err(Error::Other(
+                "A partition offset can only be used with read-only volumes".to_owned(),
+            ));
+        }
+
+        let source_size = file_handle.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+
+        if self.partition_offset > source_size {
+            return Err(Error::Other(format!(
+                "Partition offset {} is past the end of the source ({} bytes)",
+                self.partition_offset, source_size
+            )));
+        }
+
+        Handle::open_window(
+            file_handle,
+            self.partition_offset,
+            source_size - self.partition_offset,
+        )
+        .map_err(Error::from)
+    }
+
+    fn open_with(&self, open: impl FnOnce(&Volume) -> Result<(), Error>) -> Result<Volume, Error> {
+        if let Some(codepage) = self.codepage {
+            set_codepage(codepage)?;
+        }
+
+        if let Some(verbose) = self.verbose {
+            notify::set_verbose(verbose);
+        }
+
+        let volume = Volume::initialize()?;
+
+        let _registration = match &self.abort_handle {
+            Some(abort_handle) => Some(abort_handle.register(&volume)?),
+            None => None,
+        };
+
+        open(&volume).map_err(|e| match &self.abort_handle {
+            Some(abort_handle) if abort_handle.is_aborted() => {
+                Error::Other("Opening the volume was aborted".to_owned())
+            }
+            _ => e,
+        })?;
+
+        Ok(volume)
+    }
+}
+
+/// Aborts opening a volume (see `VolumeOptions::abort_handle`) from another thread.
+///
+/// Once aborted, opening a volume with the handle fails, so a new handle is needed for each attempt.
+#[derive(Debug, Clone, Default)]
+pub struct AbortHandle(Arc<Mutex<AbortState>>);
+
+#[derive(Debug, Default)]
+struct AbortState {
+    /// The volume which is being opened.
+    volume: Option<VolumeRef>,
+    aborted: bool,
+}
+
+// The volume pointer is only used to signal libfsntfs, which is safe from any thread,
+// and is cleared (under the lock) before the volume can be freed.
+unsafe impl Send for AbortState {}
+
+impl AbortHandle {
+    pub fn new() -> Self {
+        AbortHandle::default()
+    }
+
+    pub fn abort(&self) {
+        let mut state = self.lock();
+        state.aborted = true;
+
+        if let Some(volume) = state.volume {
+            let mut error = ptr::null_mut();
+
+            if unsafe { libfsntfs_volume_signal_abort(volume, &mut error) } != 1 {
+                error!("`libfsntfs_volume_signal_abort` failed!");
+            }
+        }
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.lock().aborted
+    }
+
+    fn lock(&self) -> MutexGuard<AbortState> {
+        // The state is consistent even if a thread panicked while holding the lock.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn register(&self, volume: &Volume) -> Result<AbortRegistration, Error> {
+        let mut state = self.lock();
+
+        if state.aborted {
+            return Err(Error::Other("Opening the volume was aborted".to_owned()));
+        }
+
+        state.volume = Some(volume.as_type_ref());
+
+        Ok(AbortRegistration(self))
+    }
+}
+
+/// Unregisters the volume from the abort handle when the open is done.
+struct AbortRegistration<'a>(&'a AbortHandle);
+
+impl<'a> Drop for AbortRegistration<'a> {
+    fn drop(&mut self) {
+        self.0.lock().volume = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codepage::get_codepage;
+    use crate::fixtures::*;
+    use std::fs;
+    use std::io::Cursor;
+    use tempdir::TempDir;
+
+    const PARTITION_OFFSET: u64 = 1024 * 1024;
+
+    fn sample_volume_name() -> String {
+        sample_volume().unwrap().get_name().unwrap()
+    }
+
+    #[test]
+    fn test_open_with_default_options() {
+        let volume = VolumeOptions::new().open(sample_volume_path()).unwrap();
+
+        assert_eq!(volume.get_name().unwrap(), sample_volume_name());
+    }
+
+    #[test]
+    fn test_open_with_partition_offset() {
+        let dir = TempDir::new("libfsntfs").unwrap();
+        let disk_path = dir.path().join("disk.dd");
+
+        let mut disk = vec![0; PARTITION_OFFSET as usize];
+        disk.extend(fs::read(sample_volume_path()).unwrap());
+        fs::write(&disk_path, &disk).unwrap();
+
+        let options = VolumeOptions::new().partition_offset(PARTITION_OFFSET);
+
+        let volume = options.open(disk_path.to_str().unwrap()).unwrap();
+        assert_eq!(volume.get_name().unwrap(), sample_volume_name());
+
+        let volume = options.open_reader(Cursor::new(disk)).unwrap();
+        assert_eq!(volume.get_name().unwrap(), sample_volume_name());
+
+        assert!(VolumeOptions::new()
+            .open(disk_path.to_str().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_open_reader() {
+        let image = fs::read(sample_volume_path()).unwrap();
+        let volume = VolumeOptions::new()
+            .open_reader(Cursor::new(image))
+            .unwrap();
+
+        assert_eq!(volume.get_name().unwrap(), sample_volume_name());
+        assert!(volume.get_file_entry_by_mft_idx(0).is_ok());
+    }
+
+    #[test]
+    fn test_open_with_cache() {
+        let volume = VolumeOptions::new()
+            .cache(CacheOptions::new().page_size(4096))
+            .open(sample_volume_path())
+            .unwrap();
+
+        assert_eq!(volume.get_name().unwrap(), sample_volume_name());
+        assert!(volume.iter_entries().unwrap().filter_map(|entry| entry.ok()).count() > 0);
+
+        let writable = VolumeOptions::new()
+            .cache(CacheOptions::new())
+            .read_only(false)
+            .open(sample_volume_path());
+        assert!(writable.is_err());
+    }
+
+    #[test]
+    fn test_open_with_codepage() {
+        let _codepage = lock_codepage();
+        let volume = VolumeOptions::new()
+            .codepage(Codepage::Windows1252)
+            .open(sample_volume_path());
+
+        assert!(volume.is_ok());
+        assert_eq!(get_codepage().unwrap(), Codepage::Windows1252);
+    }
+
+    #[test]
+    fn test_aborted_open_fails() {
+        let abort_handle = AbortHandle::new();
+        abort_handle.abort();
+
+        let volume = VolumeOptions::new()
+            .abort_handle(&abort_handle)
+            .open(sample_volume_path());
+
+        assert!(volume.is_err());
+        assert!(abort_handle.is_aborted());
+    }
+}
@@ -0,0 +1,63 @@
+//! `forward_to_log` installs a process-wide logger and enables libfsntfs' process-wide verbose
+//! output, so it is tested in its own binary, where no other test runs concurrently.
+#![cfg(all(unix, feature = "verbose_output"))]
+
+use libfsntfs_rs::notify::{forward_to_log, set_verbose, LOG_TARGET};
+use libfsntfs_rs::volume_options::VolumeOptions;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Counts the records forwarded from libfsntfs.
+struct NativeRecordCounter(AtomicUsize);
+
+impl Log for NativeRecordCounter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == LOG_TARGET
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static NATIVE_RECORDS: NativeRecordCounter = NativeRecordCounter(AtomicUsize::new(0));
+
+fn sample_volume_path() -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join("ntfs-img-kw-1.dd")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_forward_to_log() {
+    log::set_logger(&NATIVE_RECORDS).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    forward_to_log().unwrap();
+    forward_to_log().unwrap();
+
+    assert!(VolumeOptions::new()
+        .verbose(true)
+        .open(sample_volume_path())
+        .is_ok());
+
+    set_verbose(false);
+
+    // The records are forwarded by a background thread.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while NATIVE_RECORDS.0.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(NATIVE_RECORDS.0.load(Ordering::SeqCst) > 0);
+}
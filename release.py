@@ -37,9 +37,9 @@ class SemVer:
         return f"{self.major}.{self.minor}.{self.patch}"
 
 
-LIBYAL_LIBRARIES_DIRECTORIES = ["common", "common-build", "libcerror-sys", "libbfio-sys", "libbfio", "libfsntfs-sys",
+LIBYAL_LIBRARIES_DIRECTORIES = ["common-build", "libcerror-sys", "common", "libbfio-sys", "libbfio", "libfsntfs-sys",
                                 "libfsntfs"]
-LIBYAL_LIBRARIES_PACKAGES = ["libyal-rs-common", "libyal-rs-common-build", "libcerror-sys", "libbfio-sys",
+LIBYAL_LIBRARIES_PACKAGES = ["libyal-rs-common-build", "libcerror-sys", "libyal-rs-common", "libbfio-sys",
                              "libfsntfs-sys", "libbfio-rs", "libfsntfs-rs"]
 
 